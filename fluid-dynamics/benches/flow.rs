@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+use fluid_dynamics::{FluidGrid, SolidGrid};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+/*
+ * Times flow on grids of increasing size once their connections are cached,
+ * run with `cargo bench --features rayon` to compare the serial and parallel
+ * solvers
+ */
+const SIZES: [usize; 3] = [64, 256, 512];
+const STEPS: u32 = 20;
+
+fn run(size: usize, configure: impl Fn(&mut FluidGrid)) -> Duration {
+    let (mut grid, walls) = common::setup(size, size);
+    configure(&mut grid);
+    grid.flow(&walls);
+    let start = Instant::now();
+    grid.step(STEPS, &walls);
+    start.elapsed() / STEPS
+}
+
+/*
+ * A single puff in the corner of a large grid, where most chunks stay asleep
+ */
+fn puff(size: usize) -> Duration {
+    let walls = SolidGrid::new(size, size);
+    let mut grid = FluidGrid::new(size, size);
+    grid.set_fluid([8, 8], 100f32).unwrap();
+    grid.flow(&walls);
+    let start = Instant::now();
+    grid.step(STEPS, &walls);
+    start.elapsed() / STEPS
+}
+
+fn main() {
+    for size in SIZES.iter() {
+        let serial = run(*size, |_grid| {
+            #[cfg(feature = "rayon")]
+            _grid.set_parallel(false);
+        });
+        println!("{0}x{0} serial:   {1:?} per step", size, serial);
+
+        #[cfg(feature = "rayon")]
+        {
+            let parallel = run(*size, |grid| grid.set_parallel(true));
+            println!("{0}x{0} parallel: {1:?} per step ({2:.2}x)", size, parallel,
+                serial.as_secs_f64() / parallel.as_secs_f64());
+        }
+    }
+
+    println!("512x512 puff:     {:?} per step", puff(512));
+}
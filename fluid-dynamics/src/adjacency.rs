@@ -0,0 +1,327 @@
+use mint::{Point2, Vector2};
+
+use crate::{ConnectionGrid};
+
+/*
+ * The connections of every cell, cached between steps so the solver does not
+ * have to ask the connection grid again. Every cell has a fixed number of slots
+ * for its outgoing edges, and knows which edges end in it so the solver can
+ * gather incoming fluid without writing into other cells. Incoming edges are
+ * kept sorted, so patching a single cell gives the same order as a full build.
+ * Open cells also remember which of their neighbours are solid
+ */
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Adjacency {
+    width: usize,
+    height: usize,
+    built: bool,
+    slots: usize,
+    in_slots: usize,
+    reach: usize,
+    divisor: f32,
+    open: Vec<bool>,
+    out_count: Vec<usize>,
+    target: Vec<usize>,
+    weight: Vec<f32>,
+    direction: Vec<Vector2<f32>>,
+    total_weight: Vec<f32>,
+    in_count: Vec<usize>,
+    incoming: Vec<usize>,
+    wall_slots: usize,
+    wall_count: Vec<usize>,
+    walls: Vec<usize>,
+    stale: Vec<usize>,
+    marked: Vec<bool>,
+    closed: Vec<usize>,
+}
+
+impl Adjacency {
+    pub(crate) fn invalidate_all(&mut self) {
+        self.built = false;
+    }
+
+    /*
+     * Marks a cell and every cell that could connect to it for rebuilding
+     */
+    pub(crate) fn invalidate_cell(&mut self, pos: Point2<usize>) {
+        if !self.built || pos.x >= self.width || pos.y >= self.height {
+            return;
+        }
+        let reach = self.reach as isize;
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let (x, y) = (pos.x as isize + dx, pos.y as isize + dy);
+                if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+                    continue;
+                }
+                let idx = x as usize + y as usize * self.width;
+                if !self.marked[idx] {
+                    self.marked[idx] = true;
+                    self.stale.push(idx);
+                }
+            }
+        }
+    }
+
+    /*
+     * Brings the cache up to date, only asking the connection grid about the
+     * cells invalidated since the last update
+     */
+    pub(crate) fn update(&mut self, width: usize, height: usize, connection_grid: &impl ConnectionGrid) {
+        self.closed.clear();
+        if !self.built || self.width != width || self.height != height {
+            self.rebuild(width, height, connection_grid);
+            return;
+        }
+        for i in 0..self.stale.len() {
+            let idx = self.stale[i];
+            self.marked[idx] = false;
+            if connection_grid.is_solid(self.point(idx)) {
+                self.closed.push(idx);
+            }
+            self.unlink_cell(idx);
+        }
+        for i in 0..self.stale.len() {
+            let idx = self.stale[i];
+            let from = self.point(idx);
+            let connections = connection_grid.get_connections(from);
+            if !self.connect(idx, !connection_grid.is_solid(from), &connections, connection_grid) {
+                self.rebuild(width, height, connection_grid);
+                return;
+            }
+        }
+        self.stale.clear();
+    }
+
+    fn rebuild(&mut self, width: usize, height: usize, connection_grid: &impl ConnectionGrid) {
+        let neighbourhood = connection_grid.neighbourhood();
+        let cells = width * height;
+        let point = |idx: usize| -> Point2<usize> { [idx % width, idx / width].into() };
+        let open = (0..cells)
+            .map(|idx| !connection_grid.is_solid(point(idx)))
+            .collect::<Vec<_>>();
+        let connections = (0..cells)
+            .map(|idx| connection_grid.get_connections(point(idx)))
+            .collect::<Vec<_>>();
+
+        let mut in_count = vec![0usize; cells];
+        for (idx, targets) in connections.iter().enumerate() {
+            if open[idx] {
+                targets
+                    .iter()
+                    .filter(|to| to.x < width && to.y < height)
+                    .for_each(|to| in_count[to.x + to.y * width] += 1);
+            }
+        }
+        let size = neighbourhood.size();
+        let slots = connections.iter().map(|c| c.len()).max().unwrap_or(0).max(size);
+        let in_slots = in_count.iter().copied().max().unwrap_or(0).max(size);
+        let reach = [[0, 0], [0, 1]]
+            .iter()
+            .flat_map(|pos| neighbourhood.offsets(*pos))
+            .map(|offset| offset.dx.unsigned_abs().max(offset.dy.unsigned_abs()))
+            .max()
+            .unwrap_or(0);
+
+        *self = Adjacency {
+            width,
+            height,
+            built: true,
+            slots,
+            in_slots,
+            reach,
+            divisor: neighbourhood.total_weight() + 1f32,
+            open: vec![false; cells],
+            out_count: vec![0; cells],
+            target: vec![0; cells * slots],
+            weight: vec![0f32; cells * slots],
+            direction: vec![Vector2 { x: 0f32, y: 0f32 }; cells * slots],
+            total_weight: vec![0f32; cells],
+            in_count: vec![0; cells],
+            incoming: vec![0; cells * in_slots],
+            wall_slots: size,
+            wall_count: vec![0; cells],
+            walls: vec![0; cells * size],
+            stale: vec![],
+            marked: vec![false; cells],
+            closed: (0..cells).filter(|idx| !open[*idx]).collect(),
+        };
+        for (idx, targets) in connections.iter().enumerate() {
+            self.connect(idx, open[idx], targets, connection_grid);
+        }
+    }
+
+    /*
+     * Fills the slots of a cell from its connections, weighted by their
+     * conductance. Connections into solid cells are left out, whatever the
+     * connection grid lists. Only open cells send fluid, so only their edges
+     * are linked into the incoming lists. Returns false if the cell no longer
+     * fits in the slots it has
+     */
+    fn connect(&mut self, idx: usize, open: bool, connections: &[Point2<usize>], connection_grid: &impl ConnectionGrid) -> bool {
+        let from = self.point(idx);
+        let (width, height) = (self.width, self.height);
+        self.open[idx] = open;
+        for to in connections.iter().filter(|to| to.x < width && to.y < height && **to != from) {
+            if connection_grid.is_solid(*to) {
+                continue;
+            }
+            let conductance = connection_grid.conductance(from, *to).min(1f32);
+            if conductance.is_nan() || conductance <= 0f32 {
+                continue;
+            }
+            if self.out_count[idx] == self.slots {
+                return false;
+            }
+            let edge = idx * self.slots + self.out_count[idx];
+            let target = to.x + to.y * self.width;
+            let weight = connection_grid.neighbourhood().weight(from, *to).unwrap_or(1f32) * conductance;
+            if !weight.is_finite() || weight <= 0f32 {
+                continue;
+            }
+            self.target[edge] = target;
+            self.weight[edge] = weight;
+            self.direction[edge] = direction(from, *to);
+            self.out_count[idx] += 1;
+            self.reach = self.reach
+                .max(from.x.abs_diff(to.x))
+                .max(from.y.abs_diff(to.y));
+            if open {
+                self.total_weight[idx] += weight;
+                if !self.link(edge, target) {
+                    return false;
+                }
+            }
+        }
+        if open {
+            let neighbours = connection_grid.neighbourhood().neighbours(from, width, height);
+            for (pos, _) in neighbours.into_iter().filter(|(pos, _)| connection_grid.is_solid(*pos)) {
+                if self.wall_count[idx] == self.wall_slots {
+                    return false;
+                }
+                self.walls[idx * self.wall_slots + self.wall_count[idx]] = pos.x + pos.y * width;
+                self.wall_count[idx] += 1;
+            }
+        }
+        true
+    }
+
+    fn link(&mut self, edge: usize, target: usize) -> bool {
+        let count = self.in_count[target];
+        if count == self.in_slots {
+            return false;
+        }
+        let start = target * self.in_slots;
+        let list = &mut self.incoming[start..start + count + 1];
+        let at = list[..count].partition_point(|e| *e < edge);
+        list.copy_within(at..count, at + 1);
+        list[at] = edge;
+        self.in_count[target] += 1;
+        true
+    }
+
+    fn unlink_cell(&mut self, idx: usize) {
+        if self.open[idx] {
+            for edge in self.outgoing(idx) {
+                let target = self.target[edge];
+                let (start, count) = (target * self.in_slots, self.in_count[target]);
+                let list = &mut self.incoming[start..start + count];
+                if let Some(at) = list.iter().position(|e| *e == edge) {
+                    list.copy_within(at + 1..count, at);
+                    self.in_count[target] -= 1;
+                }
+            }
+        }
+        self.out_count[idx] = 0;
+        self.wall_count[idx] = 0;
+        self.total_weight[idx] = 0f32;
+        self.open[idx] = false;
+    }
+
+    fn point(&self, idx: usize) -> Point2<usize> {
+        [idx % self.width, idx / self.width].into()
+    }
+
+    /*
+     * How many cells away along either axis a connection reaches at most
+     */
+    pub(crate) fn reach(&self) -> usize {
+        self.reach
+    }
+
+    pub(crate) fn is_open(&self, idx: usize) -> bool {
+        self.open[idx]
+    }
+
+    /*
+     * Whether the cell was solid when the cache was last updated, cells that
+     * were invalidated since are not known to be solid
+     */
+    pub(crate) fn is_known_solid(&self, idx: usize) -> bool {
+        self.built && idx < self.open.len() && !self.marked[idx] && !self.open[idx]
+    }
+
+    /*
+     * The solid cells among those the last update looked up again, which is
+     * every solid cell after a full rebuild
+     */
+    pub(crate) fn closed(&self) -> &[usize] {
+        &self.closed
+    }
+
+    /*
+     * One more than the summed weight of a full neighbourhood, so a cell never
+     * hands out more than it keeps to a single neighbour
+     */
+    pub(crate) fn divisor(&self) -> f32 {
+        self.divisor
+    }
+
+    pub(crate) fn outgoing(&self, idx: usize) -> std::ops::Range<usize> {
+        let start = idx * self.slots;
+        start..start + self.out_count[idx]
+    }
+
+    pub(crate) fn incoming(&self, idx: usize) -> &[usize] {
+        let start = idx * self.in_slots;
+        &self.incoming[start..start + self.in_count[idx]]
+    }
+
+    /*
+     * The solid neighbours of an open cell
+     */
+    pub(crate) fn walls(&self, idx: usize) -> &[usize] {
+        let start = idx * self.wall_slots;
+        &self.walls[start..start + self.wall_count[idx]]
+    }
+
+    pub(crate) fn source(&self, edge: usize) -> usize {
+        edge / self.slots
+    }
+
+    pub(crate) fn target(&self, edge: usize) -> usize {
+        self.target[edge]
+    }
+
+    pub(crate) fn weight(&self, edge: usize) -> f32 {
+        self.weight[edge]
+    }
+
+    pub(crate) fn direction(&self, edge: usize) -> Vector2<f32> {
+        self.direction[edge]
+    }
+
+    /*
+     * The summed neighbourhood weight of the connections an open cell has,
+     * zero for cells that can not flow anywhere
+     */
+    pub(crate) fn total_weight(&self, idx: usize) -> f32 {
+        self.total_weight[idx]
+    }
+}
+
+pub(crate) fn direction(from: Point2<usize>, to: Point2<usize>) -> Vector2<f32> {
+    let (dx, dy) = (to.x as f32 - from.x as f32, to.y as f32 - from.y as f32);
+    let length = (dx * dx + dy * dy).sqrt();
+    Vector2 { x: dx / length, y: dy / length }
+}
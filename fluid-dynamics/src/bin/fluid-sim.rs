@@ -0,0 +1,271 @@
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{PathBuf};
+use std::process;
+
+use fluid_dynamics::{ColourRamp, ConnectionGrid, Field, FlowReport, FluidGrid, Frame, HeatMap, Neighbourhood, SolidGrid, Source, SourceKind};
+
+const USAGE: &str = "usage: fluid-sim MAP [options]
+
+Runs the fluid simulation on a map of '#' walls and '.' floor without any
+graphics. Positions are X,Y counted from the top left corner.
+
+options:
+  --steps N             steps to run (default 100)
+  --fluid X,Y,AMOUNT    put fluid in a cell before the first step
+  --emitter X,Y,RATE    add fluid to a cell every step
+  --drain X,Y,RATE      remove fluid from a cell every step
+  --viscocity V         viscocity of the fluid (default 1)
+  --substeps N          solver passes per step (default 1)
+  --moore               connect diagonal neighbours as well
+  --format FORMAT       grid, csv, ppm, png or gif (default grid), png and
+                        gif need the features of the same name
+  --every N             output every N steps, grid and images only show the
+                        last step unless given
+  --out DIR             directory for images (default .)
+  --field FIELD         fluid, pressure or velocity drawn in images
+                        (default fluid)
+  --ramp RAMP           heat, grey or ocean colours for images (default heat)
+  --scale N             pixels per cell in images (default 8)
+  --max LEVEL           value drawn at the top of the ramp in images, taken
+                        from each image if left out";
+
+const VALUED: [&str; 13] = [
+    "--steps", "--fluid", "--emitter", "--drain", "--viscocity", "--substeps",
+    "--format", "--every", "--out", "--field", "--ramp", "--scale", "--max",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Grid,
+    Csv,
+    Ppm,
+    Png,
+    Gif,
+}
+
+#[derive(Debug)]
+struct Options {
+    map: PathBuf,
+    steps: u32,
+    fluid: Vec<([usize; 2], f32)>,
+    sources: Vec<Source>,
+    viscocity: f32,
+    substeps: u32,
+    moore: bool,
+    format: Format,
+    every: Option<u32>,
+    out: PathBuf,
+    heat_map: HeatMap,
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got '{}'", flag, value))
+}
+
+/*
+ * Parses X,Y,VALUE as given to --fluid, --emitter and --drain
+ */
+fn placement(flag: &str, value: &str) -> Result<([usize; 2], f32), String> {
+    let parts = value.split(',').collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return Err(format!("{} expects X,Y,VALUE, got '{}'", flag, value));
+    }
+    Ok(([number(flag, parts[0])?, number(flag, parts[1])?], number(flag, parts[2])?))
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        map: PathBuf::new(),
+        steps: 100,
+        fluid: vec![],
+        sources: vec![],
+        viscocity: 1f32,
+        substeps: 1,
+        moore: false,
+        format: Format::Grid,
+        every: None,
+        out: PathBuf::from("."),
+        heat_map: HeatMap::new(Field::Fluid).with_scale(8),
+    };
+    let mut map = None;
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if map.replace(PathBuf::from(&arg)).is_some() {
+                return Err(format!("unexpected argument '{}'", arg));
+            }
+            continue;
+        }
+        if arg == "--moore" {
+            options.moore = true;
+            continue;
+        }
+        if !VALUED.contains(&arg.as_str()) {
+            return Err(format!("unknown option '{}'", arg));
+        }
+        let value = args.next().ok_or_else(|| format!("{} expects a value", arg))?;
+        match arg.as_str() {
+            "--steps" => options.steps = number(&arg, &value)?,
+            "--fluid" => options.fluid.push(placement(&arg, &value)?),
+            "--emitter" | "--drain" => {
+                let (position, rate) = placement(&arg, &value)?;
+                let kind = SourceKind::Constant { rate };
+                options.sources.push(if arg == "--drain" {
+                    Source::drain(position, kind)
+                } else {
+                    Source::emitter(position, kind)
+                });
+            },
+            "--viscocity" => options.viscocity = number(&arg, &value)?,
+            "--substeps" => options.substeps = number(&arg, &value)?,
+            "--format" => {
+                options.format = match value.as_str() {
+                    "grid" => Format::Grid,
+                    "csv" => Format::Csv,
+                    "ppm" => Format::Ppm,
+                    "png" if cfg!(feature = "png") => Format::Png,
+                    "gif" if cfg!(feature = "gif") => Format::Gif,
+                    _ => return Err(format!("unknown format '{}'", value)),
+                }
+            },
+            "--every" => options.every = Some(number::<u32>(&arg, &value)?.max(1)),
+            "--out" => options.out = PathBuf::from(value),
+            "--field" => {
+                options.heat_map.field = match value.as_str() {
+                    "fluid" => Field::Fluid,
+                    "pressure" => Field::Pressure,
+                    "velocity" => Field::Velocity,
+                    _ => return Err(format!("unknown field '{}'", value)),
+                }
+            },
+            "--ramp" => {
+                options.heat_map.ramp = match value.as_str() {
+                    "heat" => ColourRamp::heat(),
+                    "grey" => ColourRamp::grey(),
+                    "ocean" => ColourRamp::ocean(),
+                    _ => return Err(format!("unknown ramp '{}'", value)),
+                }
+            },
+            "--scale" => options.heat_map = options.heat_map.clone().with_scale(number(&arg, &value)?),
+            "--max" => options.heat_map.range = Some((0f32, number(&arg, &value)?)),
+            _ => unreachable!(),
+        }
+    }
+    options.map = map.ok_or_else(|| "no map given".to_string())?;
+    Ok(options)
+}
+
+fn setup(options: &Options) -> Result<(FluidGrid, SolidGrid), String> {
+    let text = fs::read_to_string(&options.map)
+        .map_err(|e| format!("could not read {}: {}", options.map.display(), e))?;
+    let mut walls: SolidGrid = text.parse()
+        .map_err(|_| format!("{} is not a map of '#' and '.' rows of equal length", options.map.display()))?;
+    if options.moore {
+        walls = walls.with_neighbourhood(Neighbourhood::Moore);
+    }
+    let size = walls.dimensions();
+    let mut grid = FluidGrid::new(size.x, size.y);
+    grid.set_viscocity(options.viscocity).map_err(|e| format!("--viscocity: {}", e))?;
+    grid.set_substeps(options.substeps);
+    for (position, amount) in options.fluid.iter() {
+        if walls.is_solid(*position) {
+            return Err(format!("--fluid: {},{} is a wall", position[0], position[1]));
+        }
+        grid.add_fluid(*position, *amount).map_err(|e| format!("--fluid: {}", e))?;
+    }
+    for source in options.sources.iter() {
+        if walls.is_solid(source.position) {
+            return Err(format!("--emitter/--drain: {},{} is a wall", source.position.x, source.position.y));
+        }
+        grid.add_source(source.clone()).map_err(|e| format!("--emitter/--drain: {}", e))?;
+    }
+    Ok((grid, walls))
+}
+
+fn write_image(options: &Options, frame: &Frame, step: u32) -> io::Result<()> {
+    let extension = if options.format == Format::Png { "png" } else { "ppm" };
+    fs::create_dir_all(&options.out)?;
+    let file = BufWriter::new(fs::File::create(options.out.join(format!("frame_{:05}.{}", step, extension)))?);
+    match options.format {
+        #[cfg(feature = "png")]
+        Format::Png => frame.write_png(file),
+        _ => frame.write_ppm(file),
+    }
+}
+
+#[cfg(feature = "gif")]
+fn write_animation(options: &Options, frames: &[Frame]) -> io::Result<()> {
+    fs::create_dir_all(&options.out)?;
+    let file = BufWriter::new(fs::File::create(options.out.join("animation.gif"))?);
+    fluid_dynamics::write_gif(frames, 10, file)
+}
+
+#[cfg(not(feature = "gif"))]
+fn write_animation(_options: &Options, _frames: &[Frame]) -> io::Result<()> {
+    Ok(())
+}
+
+fn write_csv(out: &mut impl Write, grid: &FluidGrid, step: u32, report: &FlowReport) -> io::Result<()> {
+    let (max_fluid, max_pressure) = grid.iter_cells()
+        .fold((0f32, 0f32), |(fluid, pressure), cell| (fluid.max(cell.fluid), pressure.max(cell.pressure)));
+    writeln!(out, "{},{},{},{},{},{},{}", step, grid.total_fluid_level(), report.emitted, report.drained,
+        report.drift(), max_fluid, max_pressure)
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let (mut grid, walls) = setup(options)?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let every = options.every.unwrap_or(match options.format {
+        Format::Csv => 1,
+        _ => options.steps.max(1),
+    });
+    let mut frames = vec![];
+    let mut output = |out: &mut BufWriter<_>, grid: &FluidGrid, step: u32, report: &FlowReport| -> io::Result<()> {
+        match options.format {
+            Format::Grid => write!(out, "step {}\n{:?}", step, grid),
+            Format::Csv => write_csv(out, grid, step, report),
+            Format::Ppm | Format::Png => write_image(options, &options.heat_map.render_with_walls(grid, &walls), step),
+            Format::Gif => {
+                frames.push(options.heat_map.render_with_walls(grid, &walls));
+                Ok(())
+            },
+        }
+    };
+
+    if options.format == Format::Csv {
+        writeln!(out, "step,total,emitted,drained,drift,max_fluid,max_pressure").map_err(|e| e.to_string())?;
+    }
+    let mut report = FlowReport::empty(grid.total_fluid_level());
+    if options.every.is_some() || options.steps == 0 {
+        output(&mut out, &grid, 0, &report).map_err(|e| e.to_string())?;
+    }
+    for step in 1..=options.steps {
+        report = grid.flow(&walls);
+        if step % every == 0 || step == options.steps {
+            output(&mut out, &grid, step, &report).map_err(|e| e.to_string())?;
+        }
+    }
+    if options.format == Format::Gif {
+        write_animation(options, &frames).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+fn main() {
+    if std::env::args().any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = match parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("fluid-sim: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        },
+    };
+    if let Err(message) = run(&options) {
+        eprintln!("fluid-sim: {}", message);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,192 @@
+use mint::{Point2};
+
+/*
+ * Splits the grid into square chunks that are simulated or left asleep as a
+ * whole. Scratch buffers of the solver are laid out chunk by chunk, so the
+ * cells of one chunk are next to each other and chunks can be handed out as
+ * independent pieces of work. Every chunk gets room for the cells of a full
+ * chunk, but never for more rows or columns than the grid has
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct Chunks {
+    size: usize,
+    span: usize,
+    rows: usize,
+    width: usize,
+    height: usize,
+    across: usize,
+    down: usize,
+    chunk_of: Vec<usize>,
+    slot: Vec<usize>,
+    awake: Vec<bool>,
+    processed: Vec<bool>,
+    touched: Vec<bool>,
+    restless: Vec<bool>,
+    wet: Vec<bool>,
+}
+
+impl Chunks {
+    pub(crate) fn new(width: usize, height: usize, size: usize) -> Self {
+        let size = size.min(width.max(height)).max(1);
+        let (span, rows) = (size.min(width), size.min(height));
+        let across = width.div_ceil(size);
+        let down = height.div_ceil(size);
+        let chunk_of = (0..width * height)
+            .map(|idx| (idx % width) / size + (idx / width / size) * across)
+            .collect::<Vec<_>>();
+        let slot = (0..width * height)
+            .map(|idx| {
+                let (x, y) = (idx % width, idx / width);
+                chunk_of[idx] * span * rows + x % size + (y % size) * span
+            })
+            .collect();
+        Chunks {
+            size,
+            span,
+            rows,
+            width,
+            height,
+            across,
+            down,
+            chunk_of,
+            slot,
+            awake: vec![false; across * down],
+            processed: vec![false; across * down],
+            touched: vec![false; across * down],
+            restless: vec![false; across * down],
+            wet: vec![true; across * down],
+        }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.across * self.down
+    }
+
+    pub(crate) fn cells_per_chunk(&self) -> usize {
+        self.span * self.rows
+    }
+
+    pub(crate) fn chunk_of(&self, idx: usize) -> usize {
+        self.chunk_of[idx]
+    }
+
+    /*
+     * Where a cell lives in a buffer laid out chunk by chunk
+     */
+    pub(crate) fn slot(&self, idx: usize) -> usize {
+        self.slot[idx]
+    }
+
+    pub(crate) fn origin(&self, chunk: usize) -> Point2<usize> {
+        [(chunk % self.across) * self.size, (chunk / self.across) * self.size].into()
+    }
+
+    /*
+     * The cells of a chunk in order, together with their place in the chunk
+     */
+    pub(crate) fn cells(&self, chunk: usize) -> impl Iterator<Item = (usize, usize)> {
+        let origin = self.origin(chunk);
+        let (width, span) = (self.width, self.span);
+        let (w, h) = (span.min(width - origin.x), self.rows.min(self.height - origin.y));
+        (0..w * h).map(move |i| {
+            let (x, y) = (i % w, i / w);
+            (origin.x + x + (origin.y + y) * width, x + y * span)
+        })
+    }
+
+    pub(crate) fn is_awake(&self, chunk: usize) -> bool {
+        self.awake[chunk]
+    }
+
+    pub(crate) fn is_processed(&self, chunk: usize) -> bool {
+        self.processed[chunk]
+    }
+
+    pub(crate) fn is_touched(&self, chunk: usize) -> bool {
+        self.touched[chunk]
+    }
+
+    /*
+     * Whether any fluid was left in the chunk the last time it was processed,
+     * chunks are taken to be wet until then
+     */
+    pub(crate) fn is_wet(&self, chunk: usize) -> bool {
+        self.wet[chunk]
+    }
+
+    pub(crate) fn set_wet(&mut self, chunk: usize, wet: bool) {
+        self.wet[chunk] = wet;
+    }
+
+    pub(crate) fn awake(&self) -> &[bool] {
+        &self.awake
+    }
+
+    /*
+     * Restores which chunks are awake, waking all of them if the layout does
+     * not match
+     */
+    #[cfg(feature = "serde")]
+    pub(crate) fn set_awake(&mut self, awake: &[bool]) {
+        if awake.len() == self.awake.len() {
+            self.awake.copy_from_slice(awake);
+        } else {
+            self.wake_all();
+        }
+    }
+
+    pub(crate) fn processed(&self) -> &[bool] {
+        &self.processed
+    }
+
+    pub(crate) fn is_asleep(&self) -> bool {
+        !self.awake.iter().any(|awake| *awake)
+    }
+
+    pub(crate) fn wake_cell(&mut self, idx: usize) {
+        if idx < self.width * self.height {
+            let chunk = self.chunk_of(idx);
+            self.awake[chunk] = true;
+        }
+    }
+
+    pub(crate) fn wake_all(&mut self) {
+        self.awake.iter_mut().for_each(|awake| *awake = true);
+    }
+
+    pub(crate) fn mark_restless(&mut self, chunk: usize) {
+        self.restless[chunk] = true;
+    }
+
+    /*
+     * Awake chunks send fluid, every chunk within reach of one receives it and
+     * every chunk within reach of those may see its pressure change
+     */
+    pub(crate) fn plan(&mut self, reach: usize) {
+        let ring = reach.div_ceil(self.size);
+        Self::dilate(self.across, self.down, ring, &self.awake, &mut self.processed);
+        Self::dilate(self.across, self.down, ring, &self.processed, &mut self.touched);
+    }
+
+    /*
+     * Chunks that had no restless cells during the last pass fall asleep
+     */
+    pub(crate) fn settle(&mut self) {
+        std::mem::swap(&mut self.awake, &mut self.restless);
+        self.restless.iter_mut().for_each(|restless| *restless = false);
+    }
+
+    fn dilate(across: usize, down: usize, ring: usize, from: &[bool], to: &mut [bool]) {
+        for cy in 0..down {
+            for cx in 0..across {
+                let (x0, x1) = (cx.saturating_sub(ring), (cx + ring).min(across - 1));
+                let (y0, y1) = (cy.saturating_sub(ring), (cy + ring).min(down - 1));
+                to[cx + cy * across] = (y0..=y1).any(|y| (x0..=x1).any(|x| from[x + y * across]));
+            }
+        }
+    }
+}
@@ -1,6 +1,21 @@
 use mint::{Point2};
 
+use crate::{Neighbourhood};
+
 pub trait ConnectionGrid {
     fn get_connections<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Point2<usize>>;
     fn is_solid<T: Into<Point2<usize>>>(&self, pos: T) -> bool;
+
+    /*
+     * How much of the full flow a connection lets through, from 0.0 to 1.0.
+     * Only asked about connections returned by get_connections, leaving one
+     * out of the connections of one side makes a connection one way
+     */
+    fn conductance<T: Into<Point2<usize>>>(&self, _from: T, _to: T) -> f32 {
+        1f32
+    }
+
+    fn neighbourhood(&self) -> &Neighbourhood {
+        &Neighbourhood::VonNeumann
+    }
 }
@@ -0,0 +1,72 @@
+use mint::{Point2};
+use std::fmt;
+
+use crate::{Offset, SpeciesId};
+
+/*
+ * Why a change to the grid was refused, the grid is left as it was
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FluidError {
+    OutOfBounds(Point2<usize>),
+    UnknownSpecies(SpeciesId),
+    NegativeAmount(f32),
+    NonFinite(f32),
+    NotPositive(f32),
+    /*
+     * Only reported for cells the grid knows to be solid, which it learns from
+     * its connection grid on every step or in update_connections
+     */
+    CellSolid(Point2<usize>),
+    NoTemperature,
+    InvalidOffset(Offset),
+    InvalidChunkSize(usize),
+}
+
+impl fmt::Display for FluidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FluidError::OutOfBounds(pos) => write!(f, "{},{} is outside of the grid", pos.x, pos.y),
+            FluidError::UnknownSpecies(species) => write!(f, "species {} does not exist", species.index()),
+            FluidError::NegativeAmount(amount) => write!(f, "{} is negative", amount),
+            FluidError::NonFinite(amount) => write!(f, "{} is not a finite number", amount),
+            FluidError::NotPositive(amount) => write!(f, "{} is not above zero", amount),
+            FluidError::CellSolid(pos) => write!(f, "{},{} is solid", pos.x, pos.y),
+            FluidError::NoTemperature => write!(f, "temperature is not enabled"),
+            FluidError::InvalidOffset(offset) => {
+                write!(f, "offset {},{} with weight {} does not lead to another cell", offset.dx, offset.dy, offset.weight)
+            },
+            FluidError::InvalidChunkSize(size) => write!(f, "chunks can not be {} cells wide", size),
+        }
+    }
+}
+
+impl std::error::Error for FluidError {}
+
+/*
+ * Checks a value before it is written into the grid
+ */
+pub(crate) fn amount(value: f32) -> Result<f32, FluidError> {
+    if !value.is_finite() {
+        Err(FluidError::NonFinite(value))
+    } else if value < 0f32 {
+        Err(FluidError::NegativeAmount(value))
+    } else {
+        Ok(value)
+    }
+}
+
+pub(crate) fn positive(value: f32) -> Result<f32, FluidError> {
+    match amount(value)? {
+        value if value > 0f32 => Ok(value),
+        value => Err(FluidError::NotPositive(value)),
+    }
+}
+
+pub(crate) fn finite(value: f32) -> Result<f32, FluidError> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(FluidError::NonFinite(value))
+    }
+}
@@ -0,0 +1,55 @@
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FlowReport {
+    pub steps: u32,
+    pub before: f32,
+    pub after: f32,
+    pub mass_in: f32,
+    pub mass_out: f32,
+    pub emitted: f32,
+    pub drained: f32,
+}
+
+impl FlowReport {
+    /*
+     * Relative error we accept from summing f32 cells before calling it drift
+     */
+    const TOLERANCE: f32 = 1e-4;
+
+    pub fn empty(level: f32) -> Self {
+        FlowReport {
+            steps: 0,
+            before: level,
+            after: level,
+            mass_in: 0f32,
+            mass_out: 0f32,
+            emitted: 0f32,
+            drained: 0f32,
+        }
+    }
+
+    /*
+     * Combines the report of a later step into this one
+     */
+    pub fn merge(self, next: FlowReport) -> Self {
+        FlowReport {
+            steps: self.steps + next.steps,
+            before: self.before,
+            after: next.after,
+            mass_in: self.mass_in + next.mass_in,
+            mass_out: self.mass_out + next.mass_out,
+            emitted: self.emitted + next.emitted,
+            drained: self.drained + next.drained,
+        }
+    }
+
+    /*
+     * Change in total fluid that sources and drains do not account for
+     */
+    pub fn drift(&self) -> f32 {
+        self.after - self.before - self.emitted + self.drained
+    }
+
+    pub fn is_conserved(&self) -> bool {
+        self.drift().abs() <= Self::TOLERANCE * self.before.abs().max(1f32)
+    }
+}
@@ -1,39 +1,532 @@
 use mint::{Point2, Vector2};
+use std::collections::{VecDeque};
+use std::iter::{FusedIterator};
+use tracing::{debug, debug_span, trace_span, warn};
 
-use crate::{ConnectionGrid};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
+use crate::{ConnectionGrid, FlowReport, FluidError, HeatSource, ReactionEvent, ReactionTable, Rect, Source, SourceHandle, SourceKind, Species, SpeciesId, Temperature};
+use crate::adjacency::{Adjacency};
+use crate::chunks::{Chunks};
+use crate::error::{amount, finite, positive};
+use crate::reaction::{MIN_EXTENT};
+#[cfg(feature = "serde")]
+use crate::save::{FluidSave, SaveError, SimulationSave, TemperatureSave, SAVE_VERSION};
+
+/*
+ * How strongly velocity steers the outflow of a cell, and the smallest share a
+ * connection against the flow still gets
+ */
+const VELOCITY_BIAS: f32 = 0.75;
+const MIN_BIAS: f32 = 0.25;
+
+/*
+ * Width and height of the square chunks the grid is split into, and how much
+ * a cell may still change relative to its fluid for its chunk to fall asleep
+ */
+const CHUNK_SIZE: usize = 16;
+const SLEEP_THRESHOLD: f32 = 1e-5;
+
+#[derive(Clone)]
 pub struct FluidGrid {
     width: usize,
     height: usize,
-    viscocity: f32,
-    fluid: Vec<f32>,
+    timestep: f32,
+    substeps: u32,
+    accumulator: f64,
+    capacity: f32,
+    tick: u64,
+    next_source: u64,
+    sources: Vec<(SourceHandle, Source)>,
+    species: Vec<Species>,
+    by_density: Vec<usize>,
+    fluid: Vec<Vec<f32>>,
     pressure: Vec<f32>,
-    velocity: Vec<Vector2<i32>>
+    force: Vec<Vector2<f32>>,
+    inertia: f32,
+    velocity: Vec<Vector2<f32>>,
+    temperature: Option<Temperature>,
+    heat: Vec<f32>,
+    heat_sources: Vec<(SourceHandle, HeatSource)>,
+    adjacency: Adjacency,
+    chunks: Chunks,
+    outflow: Vec<f32>,
+    next: Vec<f32>,
+    conducted: Vec<f32>,
+    #[cfg(feature = "rayon")]
+    parallel: bool,
 }
 
 impl FluidGrid {
     pub fn new(width: usize, height: usize) -> FluidGrid {
-        FluidGrid {
+        FluidGrid::with_species(width, height, vec![Species::default()])
+    }
+
+    pub fn with_species(width: usize, height: usize, species: Vec<Species>) -> FluidGrid {
+        let species = if species.is_empty() {
+            vec![Species::default()]
+        } else {
+            species
+        };
+        let mut grid = FluidGrid {
             width,
             height,
-            viscocity: 1f32,
-            fluid: vec![0f32; width * height],
+            timestep: 1f32,
+            substeps: 1,
+            accumulator: 0f64,
+            capacity: f32::INFINITY,
+            tick: 0,
+            next_source: 0,
+            sources: vec![],
+            fluid: vec![vec![0f32; width * height]; species.len()],
+            species,
+            by_density: vec![],
             pressure: vec![0f32; width * height],
-            velocity: vec![Vector2 { x: 0i32, y: 0i32}; width * height],
+            force: vec![Vector2 { x: 0f32, y: 0f32 }; width * height],
+            inertia: 0.75,
+            velocity: vec![Vector2 { x: 0f32, y: 0f32 }; width * height],
+            temperature: None,
+            heat: vec![],
+            heat_sources: vec![],
+            adjacency: Adjacency::default(),
+            chunks: Chunks::new(width, height, CHUNK_SIZE),
+            outflow: vec![],
+            next: vec![],
+            conducted: vec![],
+            #[cfg(feature = "rayon")]
+            parallel: true,
+        };
+        grid.sort_by_density();
+        grid
+    }
+
+    /*
+     * Sets the viscocity of every species, use set_species_viscocity to change
+     * a single one. Setting viscocity higher than 1.0 does not really make
+     * sense physcially
+     */
+    pub fn set_viscocity(&mut self, viscocity: f32) -> Result<(), FluidError> {
+        let viscocity = amount(viscocity)?;
+        self.species.iter_mut().for_each(|species| species.viscocity = viscocity);
+        self.chunks.wake_all();
+        Ok(())
+    }
+
+    pub fn set_species_viscocity(&mut self, species: SpeciesId, viscocity: f32) -> Result<(), FluidError> {
+        let viscocity = amount(viscocity)?;
+        self.species
+            .get_mut(species.0)
+            .ok_or(FluidError::UnknownSpecies(species))?
+            .viscocity = viscocity;
+        self.chunks.wake_all();
+        Ok(())
+    }
+
+    /*
+     * The amount of time simulated by a single step, flow rates are per unit of
+     * time so halving the timestep halves how far fluid moves in one step.
+     * The timestep has to be above zero
+     */
+    pub fn set_timestep(&mut self, timestep: f32) -> Result<(), FluidError> {
+        self.timestep = positive(timestep)?;
+        Ok(())
+    }
+
+    pub fn timestep(&self) -> f32 {
+        self.timestep
+    }
+
+    /*
+     * Splits every step into smaller passes of the solver, which keeps fast
+     * flowing fluids from overshooting
+     */
+    pub fn set_substeps(&mut self, substeps: u32) {
+        self.substeps = substeps.max(1);
+    }
+
+    pub fn substeps(&self) -> u32 {
+        self.substeps
+    }
+
+    /*
+     * How far the simulation is between the last step and the next one, in the
+     * range 0.0 to 1.0, for interpolating when rendering
+     */
+    pub fn interpolation(&self) -> f32 {
+        (self.accumulator / self.timestep as f64).max(0f64) as f32
+    }
+
+    /*
+     * How much of its velocity fluid keeps from one step to the next, 0.0 makes
+     * velocity follow the flow of the last step only
+     */
+    pub fn set_inertia(&mut self, inertia: f32) -> Result<(), FluidError> {
+        self.inertia = finite(inertia)?.clamp(0f32, 1f32);
+        Ok(())
+    }
+
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    /*
+     * Spreads the solver over the rayon thread pool, the results are identical
+     * to running it serially
+     */
+    #[cfg(feature = "rayon")]
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn parallel(&self) -> bool {
+        self.parallel
+    }
+
+    /*
+     * The capacity is shared by all species in a cell, when a cell holds more
+     * than this the lightest species are pushed out into the neighbouring cells.
+     * It is the only value that may be infinite, which is also the default
+     */
+    pub fn set_capacity(&mut self, capacity: f32) -> Result<(), FluidError> {
+        if capacity != f32::INFINITY {
+            amount(capacity)?;
+        }
+        self.capacity = capacity;
+        self.chunks.wake_all();
+        Ok(())
+    }
+
+    pub fn capacity(&self) -> f32 {
+        self.capacity
+    }
+
+    pub fn add_species(&mut self, species: Species) -> Result<SpeciesId, FluidError> {
+        amount(species.viscocity)?;
+        finite(species.density)?;
+        Ok(self.push_species(species))
+    }
+
+    fn push_species(&mut self, species: Species) -> SpeciesId {
+        self.species.push(species);
+        self.fluid.push(vec![0f32; self.width * self.height]);
+        self.sort_by_density();
+        SpeciesId(self.species.len() - 1)
+    }
+
+    pub fn species_id(&self, name: &str) -> Option<SpeciesId> {
+        self.species
+            .iter()
+            .position(|species| species.name == name)
+            .map(SpeciesId)
+    }
+
+    pub fn get_species(&self, species: SpeciesId) -> Option<&Species> {
+        self.species.get(species.0)
+    }
+
+    pub fn species(&self) -> impl Iterator<Item = (SpeciesId, &Species)> {
+        self.species
+            .iter()
+            .enumerate()
+            .map(|(i, species)| (SpeciesId(i), species))
+    }
+
+    pub fn add_source(&mut self, source: Source) -> Result<SourceHandle, FluidError> {
+        self.check_source(&source)?;
+        let handle = SourceHandle(self.next_source);
+        self.next_source += 1;
+        self.sources.push((handle, source));
+        Ok(handle)
+    }
+
+    fn check_source(&self, source: &Source) -> Result<(), FluidError> {
+        self.writable_index(source.species, source.position)?;
+        match source.kind {
+            SourceKind::Constant { rate } => amount(rate)?,
+            SourceKind::Pulsed { amount: pulse, .. } => amount(pulse)?,
+            SourceKind::Reservoir { rate, remaining } => amount(rate).and(amount(remaining))?,
+            SourceKind::PressureLimited { rate, limit } => amount(rate).and(finite(limit))?,
+        };
+        Ok(())
+    }
+
+    pub fn remove_source(&mut self, handle: SourceHandle) -> Option<Source> {
+        let i = self.sources.iter().position(|(h, _)| *h == handle)?;
+        Some(self.sources.remove(i).1)
+    }
+
+    pub fn get_source(&self, handle: SourceHandle) -> Option<&Source> {
+        self.sources
+            .iter()
+            .find(|(h, _)| *h == handle)
+            .map(|(_, source)| source)
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = (SourceHandle, &Source)> {
+        self.sources
+            .iter()
+            .map(|(handle, source)| (*handle, source))
+    }
+
+    pub fn pause_source(&mut self, handle: SourceHandle) -> bool {
+        self.set_source_paused(handle, true)
+    }
+
+    pub fn resume_source(&mut self, handle: SourceHandle) -> bool {
+        self.set_source_paused(handle, false)
+    }
+
+    /*
+     * Fluid and heat sources share their handles, so either can be paused
+     */
+    fn set_source_paused(&mut self, handle: SourceHandle, paused: bool) -> bool {
+        if let Some((_, source)) = self.heat_sources.iter_mut().find(|(h, _)| *h == handle) {
+            source.paused = paused;
+            return true;
+        }
+        match self.sources.iter_mut().find(|(h, _)| *h == handle) {
+            Some((_, source)) => {
+                source.paused = paused;
+                true
+            },
+            None => false
+        }
+    }
+
+    /*
+     * Gives every cell a temperature, starting out at the ambient temperature.
+     * Enabling it again only changes the settings
+     */
+    pub fn enable_temperature(&mut self, temperature: Temperature) -> Result<(), FluidError> {
+        finite(temperature.ambient)?;
+        amount(temperature.conductivity)?;
+        amount(temperature.cooling)?;
+        finite(temperature.buoyancy)?;
+        if self.temperature.is_none() {
+            self.heat = vec![temperature.ambient; self.width * self.height];
+        }
+        self.temperature = Some(temperature);
+        self.chunks.wake_all();
+        Ok(())
+    }
+
+    pub fn disable_temperature(&mut self) {
+        self.temperature = None;
+        self.heat = vec![];
+        self.heat_sources.clear();
+        self.chunks.wake_all();
+    }
+
+    pub fn temperature(&self) -> Option<&Temperature> {
+        self.temperature.as_ref()
+    }
+
+    pub fn get_temperature<T: Into<Point2<usize>>>(&self, point: T) -> Option<f32> {
+        let point = point.into();
+        if !self.valid_position(&point) {
+            None
+        } else {
+            self.heat.get(self.index(&point)).copied()
         }
     }
 
+    pub fn set_temperature<T: Into<Point2<usize>>>(&mut self, point: T, value: f32) -> Result<f32, FluidError> {
+        let point = point.into();
+        finite(value)?;
+        if self.temperature.is_none() {
+            return Err(FluidError::NoTemperature);
+        }
+        if !self.valid_position(&point) {
+            return Err(FluidError::OutOfBounds(point));
+        }
+        let index = self.index(&point);
+        self.heat[index] = value;
+        self.chunks.wake_cell(index);
+        Ok(value)
+    }
+
+    pub fn add_heat_source(&mut self, source: HeatSource) -> Result<SourceHandle, FluidError> {
+        if self.temperature.is_none() {
+            return Err(FluidError::NoTemperature);
+        }
+        if !self.valid_position(&source.position) {
+            return Err(FluidError::OutOfBounds(source.position));
+        }
+        finite(source.temperature)?;
+        amount(source.rate)?;
+        let handle = SourceHandle(self.next_source);
+        self.next_source += 1;
+        self.heat_sources.push((handle, source));
+        Ok(handle)
+    }
+
+    pub fn remove_heat_source(&mut self, handle: SourceHandle) -> Option<HeatSource> {
+        let i = self.heat_sources.iter().position(|(h, _)| *h == handle)?;
+        Some(self.heat_sources.remove(i).1)
+    }
+
+    pub fn heat_sources(&self) -> impl Iterator<Item = (SourceHandle, &HeatSource)> {
+        self.heat_sources
+            .iter()
+            .map(|(handle, source)| (*handle, source))
+    }
+
     /*
-     * Setting viscocity higher than 1.0 does not really make sense physcially
+     * Connections between cells are cached from the connection grid on the
+     * first step. When cells of the connection grid change, invalidate them so
+     * they and the cells around them are looked up again on the next step
      */
-    pub fn set_viscocity(&mut self, viscocity: f32) {
-        self.viscocity = viscocity;
+    pub fn invalidate_cell<T: Into<Point2<usize>>>(&mut self, pos: T) {
+        let pos = pos.into();
+        self.adjacency.invalidate_cell(pos);
+        if self.valid_position(&pos) {
+            self.chunks.wake_cell(self.index(&pos));
+        }
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.adjacency.invalidate_all();
+        self.chunks.wake_all();
+    }
+
+    /*
+     * Looks up the connections a step would, so writes to solid cells are
+     * refused with CellSolid before the first step. Without it they are only
+     * caught on the first step, which moves the fluid out of those cells
+     */
+    pub fn update_connections(&mut self, connection_grid: &impl ConnectionGrid) {
+        let mut adjacency = std::mem::take(&mut self.adjacency);
+        adjacency.update(self.width, self.height, connection_grid);
+        let lost = self.clear_closed(&adjacency, connection_grid);
+        if lost > 0f64 {
+            warn!(lost, "fluid sealed into solid cells was removed");
+        }
+        self.adjacency = adjacency;
+    }
+
+    /*
+     * Only chunks where fluid is moving are simulated, the rest sleep until
+     * fluid reaches them. Changing the size wakes every chunk up. A size
+     * larger than the grid is reduced to make the whole grid a single chunk
+     */
+    pub fn set_chunk_size(&mut self, size: usize) -> Result<(), FluidError> {
+        if size == 0 {
+            return Err(FluidError::InvalidChunkSize(size));
+        }
+        self.chunks = Chunks::new(self.width, self.height, size);
+        self.chunks.wake_all();
+        Ok(())
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunks.size()
     }
 
     pub fn dimensions(&self) -> Vector2<usize> {
         [self.width, self.height].into()
     }
 
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /*
+     * Changes the size of the grid keeping the cells that are in both sizes
+     * where they are, new cells start out empty. The connection grid used for
+     * the next step has to have the new size as well
+     */
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let (old_width, old_height) = (self.width, self.height);
+        let zero = Vector2 { x: 0f32, y: 0f32 };
+        for fluid in self.fluid.iter_mut() {
+            *fluid = remap(fluid, old_width, old_height, width, height, 0f32);
+        }
+        self.pressure = remap(&self.pressure, old_width, old_height, width, height, 0f32);
+        self.force = remap(&self.force, old_width, old_height, width, height, zero);
+        self.velocity = remap(&self.velocity, old_width, old_height, width, height, zero);
+        if let Some(temperature) = self.temperature {
+            self.heat = remap(&self.heat, old_width, old_height, width, height, temperature.ambient);
+        }
+        self.width = width;
+        self.height = height;
+        self.chunks = Chunks::new(width, height, self.chunks.size());
+        self.invalidate_all();
+    }
+
+    /*
+     * A new grid holding a copy of the cells in rect, with the same species and
+     * settings. Sources stay with the grid they were added to
+     */
+    pub fn sub_grid(&self, rect: Rect) -> FluidGrid {
+        let rect = rect.intersection(&self.bounds());
+        let mut grid = FluidGrid::with_species(rect.width, rect.height, self.species.clone());
+        grid.timestep = self.timestep;
+        grid.substeps = self.substeps;
+        grid.capacity = self.capacity;
+        grid.inertia = self.inertia;
+        grid.chunks = Chunks::new(rect.width, rect.height, self.chunks.size());
+        if let Some(temperature) = self.temperature {
+            grid.temperature = Some(temperature);
+            grid.heat = vec![temperature.ambient; rect.area()];
+        }
+        #[cfg(feature = "rayon")]
+        {
+            grid.parallel = self.parallel;
+        }
+        grid.blit_from(self, rect, [0, 0]);
+        grid
+    }
+
+    /*
+     * Copies the cells in src_rect of other over the cells starting at
+     * dst_point, replacing their fluid, pressure and velocity. Species are
+     * matched by name and added when this grid does not have them yet. Both
+     * sides are clipped to their grid, the area written to is returned
+     */
+    pub fn blit_from<T: Into<Point2<usize>>>(&mut self, other: &FluidGrid, src_rect: Rect, dst_point: T) -> Rect {
+        let dst_point = dst_point.into();
+        let src = src_rect.intersection(&other.bounds());
+        let dst = Rect::new(dst_point.x, dst_point.y, src.width, src.height).intersection(&self.bounds());
+        if dst.is_empty() || src.is_empty() {
+            return Rect::new(dst_point.x, dst_point.y, 0, 0);
+        }
+
+        let mapping: Vec<Option<usize>> = {
+            let mut mapping = vec![None; self.species.len()];
+            for species in other.species.iter() {
+                let id = match self.species_id(&species.name) {
+                    Some(id) => id,
+                    None => {
+                        mapping.push(None);
+                        self.push_species(species.clone())
+                    }
+                };
+                if let Some(from) = other.species_id(&species.name) {
+                    mapping[id.0] = Some(from.0);
+                }
+            }
+            mapping
+        };
+
+        for pos in dst.points() {
+            let from = other.index(&[src.x + pos.x - dst.x, src.y + pos.y - dst.y].into());
+            let to = self.index(&pos);
+            for (species, fluid) in self.fluid.iter_mut().enumerate() {
+                fluid[to] = mapping[species].map_or(0f32, |from_species| other.fluid[from_species][from]);
+            }
+            self.pressure[to] = other.pressure[from];
+            self.force[to] = other.force[from];
+            self.velocity[to] = other.velocity[from];
+            if let Some(temperature) = self.temperature {
+                self.heat[to] = other.heat.get(from).copied().unwrap_or(temperature.ambient);
+            }
+            self.chunks.wake_cell(to);
+        }
+        dst
+    }
+
     fn index(&self, point: &Point2<usize>) -> usize {
         point.x + (point.y * self.width)
     }
@@ -43,12 +536,25 @@ impl FluidGrid {
     }
 
     pub fn get_fluid<T: Into<Point2<usize>>>(&self, point: T) -> Option<&f32> {
+        self.get_species_fluid(SpeciesId::default(), point)
+    }
+
+    pub fn get_species_fluid<T: Into<Point2<usize>>>(&self, species: SpeciesId, point: T) -> Option<&f32> {
         let point = point.into();
         if !self.valid_position(&point) {
             None
         } else {
             let index = self.index(&point);
-            self.fluid.get(index)
+            self.fluid.get(species.0)?.get(index)
+        }
+    }
+
+    pub fn get_total_fluid<T: Into<Point2<usize>>>(&self, point: T) -> Option<f32> {
+        let point = point.into();
+        if !self.valid_position(&point) {
+            None
+        } else {
+            Some(self.total_at(self.index(&point)))
         }
     }
 
@@ -62,179 +568,1403 @@ impl FluidGrid {
         }
     }
 
-    pub fn get_velocity<T: Into<Point2<usize>>>(&self, point: T) -> Option<&Vector2<i32>> {
+    /*
+     * The net push on a solid cell from the fluid around it, pointing away from
+     * the side with the most pressure. Open cells have no force
+     */
+    pub fn get_force<T: Into<Point2<usize>>>(&self, point: T) -> Option<&Vector2<f32>> {
         let point = point.into();
         if !self.valid_position(&point) {
             None
         } else {
             let index = self.index(&point);
-            self.velocity.get(index)
+            self.force.get(index)
         }
     }
 
-    pub fn add_fluid<T: Into<Point2<usize>>>(&mut self, point: T, value: f32) -> Option<f32> {
+    pub fn get_pressure_differential<T: Into<Point2<usize>>>(&self, point: T) -> Option<f32> {
+        self.get_force(point)
+            .map(|force| (force.x * force.x + force.y * force.y).sqrt())
+    }
+
+    pub fn get_velocity<T: Into<Point2<usize>>>(&self, point: T) -> Option<&Vector2<f32>> {
         let point = point.into();
-        let index = self.index(&point);
-        if let Some(fluid) = self.fluid.get(index) {
-            println!("adding fluid: {:.4} + {}", fluid, value);
-            let n = fluid + value;
-            println!("new: {:.4}", n);
-            self.fluid[index] = n;
-            Some(n)
-        } else {
+        if !self.valid_position(&point) {
             None
+        } else {
+            let index = self.index(&point);
+            self.velocity.get(index)
         }
     }
 
-    pub fn set_fluid<T: Into<Point2<usize>>>(&mut self, point: T, value: f32) -> Option<f32> {
-        let point = point.into();
+    /*
+     * Adds to or, with a negative value, removes fluid from a cell and returns
+     * the new amount. Removing more than the cell holds is refused
+     */
+    pub fn add_fluid<T: Into<Point2<usize>>>(&mut self, point: T, value: f32) -> Result<f32, FluidError> {
+        self.add_species_fluid(SpeciesId::default(), point, value)
+    }
+
+    pub fn add_species_fluid<T: Into<Point2<usize>>>(&mut self, species: SpeciesId, point: T, value: f32) -> Result<f32, FluidError> {
+        finite(value)?;
+        let index = self.writable_index(species, point.into())?;
+        let fluid = amount(self.fluid[species.0][index] + value)?;
+        self.fluid[species.0][index] = fluid;
+        self.chunks.wake_cell(index);
+        Ok(fluid)
+    }
+
+    pub fn set_fluid<T: Into<Point2<usize>>>(&mut self, point: T, value: f32) -> Result<f32, FluidError> {
+        self.set_species_fluid(SpeciesId::default(), point, value)
+    }
+
+    pub fn set_species_fluid<T: Into<Point2<usize>>>(&mut self, species: SpeciesId, point: T, value: f32) -> Result<f32, FluidError> {
+        amount(value)?;
+        let index = self.writable_index(species, point.into())?;
+        self.fluid[species.0][index] = value;
+        self.chunks.wake_cell(index);
+        Ok(value)
+    }
+
+    /*
+     * The index of a cell fluid may be written to. Cells are only known to be
+     * solid once the grid has stepped with its connection grid or updated its
+     * connections, see FluidError::CellSolid
+     */
+    fn writable_index(&self, species: SpeciesId, point: Point2<usize>) -> Result<usize, FluidError> {
+        if species.0 >= self.species.len() {
+            return Err(FluidError::UnknownSpecies(species));
+        }
+        if !self.valid_position(&point) {
+            return Err(FluidError::OutOfBounds(point));
+        }
         let index = self.index(&point);
-        if index <= self.fluid.len() {
-            self.fluid.insert(index, value);
-            Some(value)
-        } else {
-            None
+        if self.adjacency.is_known_solid(index) {
+            return Err(FluidError::CellSolid(point));
         }
+        Ok(index)
     }
 
     pub fn total_fluid_level(&self) -> f32 {
         self.fluid
             .iter()
+            .flatten()
             .sum()
     }
 
-    pub fn is_stable(&self) -> bool {
-        let base = self.fluid[0];
-        let delta = 0.01;
+    pub fn total_species_level(&self, species: SpeciesId) -> f32 {
         self.fluid
-            .iter()
-            .all(|f| *f == 0f32 || (base - *f).abs() < delta)
-    }
-
-    pub fn flow(&mut self, connection_grid: &impl ConnectionGrid) {
-        let (fluid, pressure, velocity) = self.calculate_flow(connection_grid);
-        self.fluid = fluid;
-        self.pressure = pressure;
-        self.velocity = velocity;
-    }
-
-    fn calculate_flow(&self, connection_grid: &impl ConnectionGrid) -> (Vec<f32>, Vec<f32>, Vec<Vector2<i32>>) {
-        let mut fluid = vec![0f32; self.width * self.height];
-        let mut pressure = vec![0f32; self.width * self.height];
-        let mut velocity = vec![Vector2 { x: 0i32, y: 0i32}; self.width * self.height];
-
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let idx = self.index(&[x, y].into());
-                let connections = connection_grid.get_connections([x, y]);
-                let flow_connections = connections
-                    .iter()
-                    .filter_map(|pos| {
-                        match self.get_fluid(*pos) {
-                            Some(f) if *f > 0f32 => {
-                                let i = self.index(&pos);
-                                let v = self.velocity[i];
-                                let vel_pos: Point2<i32> = [pos.x as i32 + v.x, pos.y as i32 + v.y].into();
-                                let connection_count = connection_grid.get_connections([pos.x, pos.y]).len();
-                                let flow_per_connection = f / 5f32;
-                                if v == [0, 0].into() || connection_count != 4 {
-                                    Some((pos.clone(), flow_per_connection))
-                                } else if vel_pos == [x as i32, y as i32].into() {
-                                    Some((pos.clone(), 5f32 * 5f32 * (flow_per_connection / 10f32)))
-                                } else {
-                                    Some((pos.clone(), 5f32 * (flow_per_connection / 10f32)))
-                                }
-                            },
-                            _ => None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                let in_flow: f32 = flow_connections
-                    .iter()
-                    .map(|(_, f)| *f)
-                    .sum();
-                if connection_grid.is_solid([x, y]) {
-                    pressure[idx] = in_flow * 5f32;
-                } else {
-                    pressure[idx] = 0f32;
-                    let out_flow: f32 = self.fluid[idx] / 5f32;
-                    let out_flow = out_flow * connections.len() as f32;
-                    fluid[idx] = self.fluid[idx] + self.viscocity * (in_flow - out_flow);
-
-                    if flow_connections.len() == 1 {
-                        let dx = x as i32 - flow_connections[0].0.x as i32;
-                        let dy = y as i32 - flow_connections[0].0.y as i32;
-                        velocity[idx] = Vector2 {
-                            x: dx,
-                            y: dy
-                        };
-                    } else {
-                        velocity[idx] = Vector2 {
-                            x: 0,
-                            y: 0
-                        };
-                    }
-                }
-            }
-        }
-        (fluid, pressure, velocity)
+            .get(species.0)
+            .map(|fluid| fluid.iter().sum())
+            .unwrap_or(0f32)
     }
 
-    pub fn print_velocity(&self) {
-        let mut buf = String::new();
-        for y in 0..self.height {
-            for x in 0..self.width {
-                if let Some(v) = self.get_velocity([x, y]) {
-                    buf.push_str(&format!(" {:02},{:02} ", v.x, v.y));
-                }
-            }
-            buf.push('\n');
+    /*
+     * The grid is stable once every chunk has fallen asleep
+     */
+    pub fn is_stable(&self) -> bool {
+        self.chunks.is_asleep()
+    }
+
+    pub fn is_region_stable<T: Into<Point2<usize>>>(&self, point: T) -> Option<bool> {
+        let point = point.into();
+        if !self.valid_position(&point) {
+            None
+        } else {
+            Some(!self.chunks.is_awake(self.chunks.chunk_of(self.index(&point))))
         }
-        println!("{}\n\n", buf)
     }
 
-    pub fn iter(&self) -> FluidIterator {
-        FluidIterator::new(&self)
+    /*
+     * The top left cell of every chunk that is still awake
+     */
+    pub fn active_regions(&self) -> impl Iterator<Item = Point2<usize>> + '_ {
+        (0..self.chunks.count())
+            .filter(move |chunk| self.chunks.is_awake(*chunk))
+            .map(move |chunk| self.chunks.origin(chunk))
     }
-}
 
-impl std::fmt::Debug for FluidGrid {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let mut buf = String::new();
-        for y in 0..self.height {
-            for x in 0..self.width {
-                if let Some(v) = self.get_fluid([x, y]) {
-                    buf.push_str(&format!(" {:06.3} ", v));
+    /*
+     * Every open cell that fluid can reach from point through the connections
+     * of connection_grid, in the order they are reached and starting with point.
+     * Empty when point is solid or outside of the grid
+     */
+    pub fn connected_cells<T: Into<Point2<usize>>>(&self, point: T, connection_grid: &impl ConnectionGrid) -> Vec<Point2<usize>> {
+        let point = point.into();
+        if !self.valid_position(&point) || connection_grid.is_solid(point) {
+            return vec![];
+        }
+        let mut seen = vec![false; self.width * self.height];
+        let mut cells = vec![];
+        let mut queue = VecDeque::from(vec![point]);
+        seen[self.index(&point)] = true;
+        while let Some(pos) = queue.pop_front() {
+            cells.push(pos);
+            for next in connection_grid.get_connections(pos) {
+                if self.valid_position(&next) && !seen[self.index(&next)] && !connection_grid.is_solid(next) {
+                    seen[self.index(&next)] = true;
+                    queue.push_back(next);
                 }
             }
-            buf.push('\n');
         }
-        writeln!(f, "{}", buf)
+        cells
     }
-}
-
-pub struct FluidIterator<'a> {
-    fluid_grid: &'a FluidGrid,
-    next: usize
-}
 
-impl<'a> FluidIterator<'a> {
-    fn new(fluid_grid: &'a FluidGrid) -> Self {
-        FluidIterator {
-            fluid_grid,
-            next: 0
+    /*
+     * The fluid of all species in the cells connected to point, like the
+     * volume of a room
+     */
+    pub fn region_fluid<T: Into<Point2<usize>>>(&self, point: T, connection_grid: &impl ConnectionGrid) -> Option<f32> {
+        let cells = self.connected_cells(point, connection_grid);
+        if cells.is_empty() {
+            None
+        } else {
+            Some(cells.iter().map(|pos| self.total_at(self.index(pos))).sum())
         }
     }
-}
 
-impl<'a> Iterator for FluidIterator<'a> {
-    type Item = (Point2<usize>, f32);
+    /*
+     * Every cell holding more than threshold of all species together, row by
+     * row
+     */
+    pub fn cells_above(&self, threshold: f32) -> Vec<Point2<usize>> {
+        self.bounds()
+            .points()
+            .filter(|pos| self.total_at(self.index(pos)) > threshold)
+            .collect()
+    }
 
-    fn next(&mut self) -> Option<(Point2<usize>, f32)> {
-        let (x, y) = (self.next % self.fluid_grid.width, self.next / self.fluid_grid.width);
-        let tile = self.fluid_grid.fluid.get(self.next)?;
-        self.next += 1;
-        Some(([x, y].into(), *tile))
+    /*
+     * The smallest rect around every cell holding more than threshold, the
+     * extent of a flood
+     */
+    pub fn bounds_above(&self, threshold: f32) -> Option<Rect> {
+        let cells = self.cells_above(threshold);
+        let first = cells.first()?;
+        let (mut min, mut max) = (*first, *first);
+        for pos in cells.iter() {
+            min = [min.x.min(pos.x), min.y.min(pos.y)].into();
+            max = [max.x.max(pos.x), max.y.max(pos.y)].into();
+        }
+        Some(Rect::new(min.x, min.y, max.x - min.x + 1, max.y - min.y + 1))
     }
-}
 
+    /*
+     * How the fluid of all species changes around point, per cell in the
+     * direction it increases the most, so fluid tends to move against it. Only
+     * the cells point connects to are taken into account, a cell without
+     * connections has no gradient
+     */
+    pub fn get_gradient<T: Into<Point2<usize>>>(&self, point: T, connection_grid: &impl ConnectionGrid) -> Option<Vector2<f32>> {
+        let point = point.into();
+        if !self.valid_position(&point) {
+            return None;
+        }
+        let here = self.total_at(self.index(&point));
+        let (mut xx, mut yy, mut xy, mut bx, mut by) = (0f32, 0f32, 0f32, 0f32, 0f32);
+        if !connection_grid.is_solid(point) {
+            for next in connection_grid.get_connections(point) {
+                if !self.valid_position(&next) {
+                    continue;
+                }
+                let (dx, dy) = (next.x as f32 - point.x as f32, next.y as f32 - point.y as f32);
+                let change = self.total_at(self.index(&next)) - here;
+                xx += dx * dx;
+                yy += dy * dy;
+                xy += dx * dy;
+                bx += dx * change;
+                by += dy * change;
+            }
+        }
+        /*
+         * Least squares fit of a plane through the neighbours, falling back to
+         * each axis on its own when they do not span both directions
+         */
+        let det = xx * yy - xy * xy;
+        let gradient = if det > 1e-6 {
+            [(yy * bx - xy * by) / det, (xx * by - xy * bx) / det]
+        } else {
+            [if xx > 0f32 { bx / xx } else { 0f32 }, if yy > 0f32 { by / yy } else { 0f32 }]
+        };
+        Some(gradient.into())
+    }
+
+    /*
+     * Runs a copy of the grid until point holds at least level of all species
+     * together and returns how many steps it took, or None if it does not
+     * happen within max_steps. The grid itself is left untouched
+     */
+    pub fn steps_until<T: Into<Point2<usize>>>(&self, point: T, level: f32, max_steps: u32, connection_grid: &impl ConnectionGrid) -> Option<u32> {
+        let point = point.into();
+        if !self.valid_position(&point) {
+            return None;
+        }
+        let index = self.index(&point);
+        if self.total_at(index) >= level {
+            return Some(0);
+        }
+        let _span = debug_span!("steps_until", x = point.x, y = point.y, level, max_steps).entered();
+        let mut fork = self.clone();
+        for step in 1..=max_steps {
+            fork.flow(connection_grid);
+            if fork.total_at(index) >= level {
+                return Some(step);
+            }
+            let idle = fork.sources.iter().all(|(_, source)| source.paused) && fork.temperature.is_none();
+            if idle && fork.is_stable() {
+                break;
+            }
+        }
+        None
+    }
+
+    fn total_at(&self, index: usize) -> f32 {
+        self.fluid
+            .iter()
+            .map(|fluid| fluid[index])
+            .sum()
+    }
+
+    /*
+     * Every step runs in a debug span named flow, with the solver stages in
+     * trace spans below it, and reports what moved as a debug event. A grid
+     * without cells only counts the step
+     */
+    pub fn flow(&mut self, connection_grid: &impl ConnectionGrid) -> FlowReport {
+        let _span = debug_span!("flow", tick = self.tick, width = self.width, height = self.height).entered();
+        if self.width * self.height == 0 {
+            self.tick += 1;
+            return FlowReport {
+                steps: 1,
+                ..FlowReport::empty(0f32)
+            };
+        }
+        let before = self.mass();
+        let rate = self.timestep / self.substeps as f32;
+        let (mut mass_in, mut mass_out) = (0f64, 0f64);
+
+        let mut adjacency = std::mem::take(&mut self.adjacency);
+        trace_span!("adjacency").in_scope(|| adjacency.update(self.width, self.height, connection_grid));
+        let lost = self.clear_closed(&adjacency, connection_grid);
+        let (emitted, drained) = self.apply_sources(&adjacency);
+        let awake = self.chunks.awake().iter().filter(|awake| **awake).count();
+        trace_span!("temperature").in_scope(|| self.update_temperature(&adjacency));
+        for substep in 0..self.substeps {
+            let (moved_in, moved_out) = trace_span!("substep", substep).in_scope(|| self.calculate_flow(&adjacency, rate));
+            mass_in += moved_in;
+            mass_out += moved_out;
+        }
+        trace_span!("pressure").in_scope(|| self.update_pressure(&adjacency));
+        self.adjacency = adjacency;
+
+        let report = FlowReport {
+            steps: 1,
+            before: before as f32,
+            after: self.mass() as f32,
+            mass_in: mass_in as f32,
+            mass_out: mass_out as f32,
+            emitted: emitted as f32,
+            drained: (drained + lost) as f32,
+        };
+        self.tick += 1;
+        debug!(awake, chunks = self.chunks.count(), moved = report.mass_out, emitted = report.emitted, drained = report.drained, "flow step");
+        if !report.is_conserved() {
+            warn!(drift = report.drift(), "fluid drifted during flow");
+        }
+        debug_assert!(report.is_conserved(), "fluid drifted by {} during flow", report.drift());
+        report
+    }
+
+    pub fn step(&mut self, steps: u32, connection_grid: &impl ConnectionGrid) -> FlowReport {
+        (0..steps).fold(FlowReport::empty(self.total_fluid_level()), |report, _| {
+            report.merge(self.flow(connection_grid))
+        })
+    }
+
+    /*
+     * Advances the simulation by dt, running as many whole steps as fit and
+     * carrying the remainder over to the next call. A dt that is negative or
+     * not finite is ignored
+     */
+    pub fn flow_for(&mut self, dt: f32, connection_grid: &impl ConnectionGrid) -> FlowReport {
+        let timestep = self.timestep as f64;
+        let mut report = FlowReport::empty(self.total_fluid_level());
+        if amount(dt).is_err() {
+            warn!(dt, "ignoring flow_for with a time that is negative or not finite");
+            return report;
+        }
+        self.accumulator += dt as f64;
+        while self.accumulator >= timestep * (1f64 - 1e-6) {
+            self.accumulator -= timestep;
+            report = report.merge(self.flow(connection_grid));
+        }
+        report
+    }
+
+    /*
+     * Fluid caught in a cell that has become solid is handed to its open
+     * neighbours by the weight of their connection. It is lost if there are
+     * none, the amount lost is returned
+     */
+    fn clear_closed(&mut self, adjacency: &Adjacency, connection_grid: &impl ConnectionGrid) -> f64 {
+        let mut lost = 0f64;
+        for idx in adjacency.closed().iter().copied() {
+            if self.fluid.iter().all(|fluid| fluid[idx] == 0f32) {
+                continue;
+            }
+            let position: Point2<usize> = [idx % self.width, idx / self.width].into();
+            let open = connection_grid.neighbourhood()
+                .neighbours(position, self.width, self.height)
+                .into_iter()
+                .map(|(pos, weight)| (pos.x + pos.y * self.width, weight))
+                .filter(|(to, _)| adjacency.is_open(*to))
+                .collect::<Vec<_>>();
+            let total: f32 = open.iter().map(|(_, weight)| weight).sum();
+            for fluid in self.fluid.iter_mut() {
+                let amount = std::mem::take(&mut fluid[idx]);
+                if total > 0f32 {
+                    open.iter().for_each(|(to, weight)| fluid[*to] += amount * weight / total);
+                } else {
+                    lost += amount as f64;
+                }
+            }
+            self.velocity[idx] = Vector2 { x: 0f32, y: 0f32 };
+            open.iter().for_each(|(to, _)| self.chunks.wake_cell(*to));
+        }
+        lost
+    }
+
+    /*
+     * Sources on cells that have become solid since they were added are left
+     * alone until the cell opens again
+     */
+    fn apply_sources(&mut self, adjacency: &Adjacency) -> (f64, f64) {
+        let (mut emitted, mut drained) = (0f64, 0f64);
+        for i in 0..self.sources.len() {
+            let source = &self.sources[i].1;
+            if !self.valid_position(&source.position) || source.species.0 >= self.species.len() {
+                continue;
+            }
+            let idx = self.index(&source.position);
+            if !adjacency.is_open(idx) {
+                continue;
+            }
+            let fluid = self.fluid[source.species.0][idx];
+            let amount = source.amount(self.tick, self.timestep, fluid, self.cell_pressure(idx));
+            self.fluid[source.species.0][idx] = (fluid + amount).max(0f32);
+            self.sources[i].1.consume(amount);
+            if amount != 0f32 {
+                self.chunks.wake_cell(idx);
+            }
+            if amount > 0f32 {
+                emitted += amount as f64;
+            } else {
+                drained -= amount as f64;
+            }
+        }
+        (emitted, drained)
+    }
+
+    /*
+     * Runs the reactions of table in every open cell of the chunks that are
+     * awake or still hold fluid, see ReactionTable::react
+     */
+    pub(crate) fn apply_reactions(&mut self, table: &ReactionTable, connection_grid: &impl ConnectionGrid, dt: f32) -> Vec<ReactionEvent> {
+        let mut events = vec![];
+        let count = self.species.len();
+        let reactions = table.reactions()
+            .filter(|(_, reaction)| {
+                reaction.inputs.iter().chain(reaction.outputs.iter()).all(|(species, _)| species.0 < count)
+            })
+            .collect::<Vec<_>>();
+        if reactions.is_empty() {
+            return events;
+        }
+
+        let mut adjacency = std::mem::take(&mut self.adjacency);
+        adjacency.update(self.width, self.height, connection_grid);
+        self.clear_closed(&adjacency, connection_grid);
+        for chunk in 0..self.chunks.count() {
+            if !self.chunks.is_awake(chunk) && !self.chunks.is_wet(chunk) {
+                continue;
+            }
+            for (idx, _) in self.chunks.cells(chunk) {
+                if !adjacency.is_open(idx) {
+                    continue;
+                }
+                let position: Point2<usize> = [idx % self.width, idx / self.width].into();
+                for (id, reaction) in reactions.iter() {
+                    let walls = adjacency.walls(idx);
+                    if reaction.surface && walls.is_empty() {
+                        continue;
+                    }
+                    let extent = reaction.extent(|species| self.fluid[species.0][idx], self.heat.get(idx).copied(), dt);
+                    if extent < MIN_EXTENT {
+                        continue;
+                    }
+
+                    for (species, ratio) in reaction.inputs.iter() {
+                        let fluid = &mut self.fluid[species.0][idx];
+                        *fluid = (*fluid - ratio * extent).max(0f32);
+                    }
+                    for (species, ratio) in reaction.outputs.iter() {
+                        self.fluid[species.0][idx] += ratio * extent;
+                    }
+                    if let Some(heat) = self.heat.get_mut(idx) {
+                        *heat += reaction.heat * extent;
+                    }
+                    self.chunks.wake_cell(idx);
+
+                    let event = |surface, share| ReactionEvent {
+                        reaction: *id,
+                        position,
+                        surface,
+                        amount: share,
+                        heat: reaction.heat * share,
+                        pressure: reaction.pressure * share,
+                        damage: reaction.damage * share,
+                    };
+                    if reaction.surface {
+                        let share = extent / walls.len() as f32;
+                        let width = self.width;
+                        events.extend(walls.iter().map(|wall| event(Some([wall % width, wall / width].into()), share)));
+                    } else {
+                        events.push(event(None, extent));
+                    }
+                }
+            }
+        }
+        self.adjacency = adjacency;
+        events
+    }
+
+    /*
+     * Heat sources pull their cells towards their temperature, then heat is
+     * conducted along the connections and cells cool towards ambient. Only
+     * awake chunks are updated, heat crossing into a sleeping chunk wakes it.
+     *
+     * Every connection touching an awake cell exchanges heat once, with both
+     * cells getting half of it in opposite directions. Connections back and
+     * forth between two cells add up, so heat is conserved by conduction even
+     * where the fluid can only go one way
+     */
+    fn update_temperature(&mut self, adjacency: &Adjacency) {
+        let temperature = match self.temperature {
+            Some(temperature) => temperature,
+            None => return,
+        };
+        let dt = self.timestep;
+        for (_, source) in self.heat_sources.iter() {
+            if source.paused || !self.valid_position(&source.position) {
+                continue;
+            }
+            let idx = self.index(&source.position);
+            let change = (source.temperature - self.heat[idx]) * (source.rate * dt).min(1f32);
+            self.heat[idx] += change;
+            if change.abs() > SLEEP_THRESHOLD {
+                self.chunks.wake_cell(idx);
+            }
+        }
+
+        let conduction = 0.5 * (temperature.conductivity * dt).min(1f32) / adjacency.divisor();
+        let cooling = (temperature.cooling * dt).min(1f32);
+        let threshold = SLEEP_THRESHOLD / temperature.buoyancy.abs().max(1f32);
+        let mut conducted = std::mem::take(&mut self.conducted);
+        conducted.resize(self.heat.len(), 0f32);
+        let mut exchange = |from: usize, to: usize, weight: f32| {
+            let heat = conduction * weight * (self.heat[to] - self.heat[from]);
+            conducted[from] += heat;
+            conducted[to] -= heat;
+        };
+        for chunk in 0..self.chunks.count() {
+            if !self.chunks.is_awake(chunk) {
+                continue;
+            }
+            for (idx, _) in self.chunks.cells(chunk) {
+                for edge in adjacency.outgoing(idx) {
+                    exchange(idx, adjacency.target(edge), adjacency.weight(edge));
+                }
+                for edge in adjacency.incoming(idx) {
+                    let from = adjacency.source(*edge);
+                    if !self.chunks.is_awake(self.chunks.chunk_of(from)) {
+                        exchange(from, idx, adjacency.weight(*edge));
+                    }
+                }
+            }
+        }
+
+        let mut woken = vec![];
+        for chunk in 0..self.chunks.count() {
+            if !self.chunks.is_awake(chunk) {
+                continue;
+            }
+            for (idx, _) in self.chunks.cells(chunk) {
+                let t = self.heat[idx] + std::mem::take(&mut conducted[idx]);
+                let t = t + (temperature.ambient - t) * cooling;
+                if (t - self.heat[idx]).abs() > threshold {
+                    self.chunks.mark_restless(chunk);
+                }
+                self.heat[idx] = t;
+                let neighbours = adjacency.outgoing(idx)
+                    .map(|edge| adjacency.target(edge))
+                    .chain(adjacency.incoming(idx).iter().map(|edge| adjacency.source(*edge)));
+                for other in neighbours {
+                    if self.chunks.is_awake(self.chunks.chunk_of(other)) {
+                        continue;
+                    }
+                    let heat = std::mem::take(&mut conducted[other]);
+                    self.heat[other] += heat;
+                    if heat.abs() > threshold {
+                        woken.push(other);
+                    }
+                }
+            }
+        }
+        for idx in woken {
+            self.chunks.wake_cell(idx);
+            self.chunks.mark_restless(self.chunks.chunk_of(idx));
+        }
+        self.conducted = conducted;
+    }
+
+    /*
+     * The weight of all the fluid resting in a cell
+     */
+    fn cell_pressure(&self, idx: usize) -> f32 {
+        self.species
+            .iter()
+            .zip(self.fluid.iter())
+            .map(|(species, fluid)| species.density * fluid[idx])
+            .sum()
+    }
+
+    fn mass(&self) -> f64 {
+        self.fluid
+            .iter()
+            .flatten()
+            .map(|f| *f as f64)
+            .sum()
+    }
+
+    /*
+     * Every open cell hands out a share of its own fluid to its connections, so
+     * whatever leaves one cell arrives in another and the total never changes.
+     * Fluid carries its velocity along with it, and the net flow through a cell
+     * pushes that velocity towards the direction the fluid is moving.
+     *
+     * The first pass works out what leaves every cell, the second has every
+     * cell gather what arrives from its neighbours. Neither writes outside the
+     * cell it is working on, which lets both run on chunks in parallel.
+     *
+     * Only cells in awake chunks send fluid, and only the chunks within reach
+     * of those gather it. Cells in sleeping chunks keep what they have, so
+     * nothing flows out of a sleeping chunk until fluid arriving or a
+     * difference in level at its border wakes it up again
+     */
+    fn calculate_flow(&mut self, adjacency: &Adjacency, rate: f32) -> (f64, f64) {
+        let count = self.species.len();
+        let divisor = adjacency.divisor();
+        self.chunks.plan(adjacency.reach());
+        let slots = self.chunks.count() * self.chunks.cells_per_chunk();
+
+        let out_stride = count + 1;
+        let mut outflow = std::mem::take(&mut self.outflow);
+        outflow.resize(slots * out_stride, 0f32);
+        self.for_chunks(&mut outflow, out_stride, self.chunks.awake(), |idx, cell| {
+            cell.iter_mut().for_each(|c| *c = 0f32);
+            let connection_weight = adjacency.total_weight(idx);
+            let weight_sum: f32 = adjacency.outgoing(idx)
+                .map(|edge| self.flow_weight(adjacency, edge))
+                .sum();
+            if connection_weight <= 0f32 || weight_sum <= 0f32 {
+                return;
+            }
+            cell[0] = weight_sum;
+            for (s, species) in self.species.iter().enumerate() {
+                let f = self.fluid[s][idx];
+                if f > 0f32 {
+                    cell[s + 1] = (species.viscocity * rate * f * connection_weight / divisor).min(f);
+                }
+            }
+        });
+
+        let stride = count + 4;
+        let mut next = std::mem::take(&mut self.next);
+        next.resize(slots * stride, 0f32);
+        self.for_chunks(&mut next, stride, self.chunks.processed(), |idx, cell| {
+            self.gather(adjacency, &outflow, rate, idx, cell)
+        });
+
+        let (mut mass_in, mut mass_out) = (0f64, 0f64);
+        let cells_per_chunk = self.chunks.cells_per_chunk();
+        for chunk in 0..self.chunks.count() {
+            if !self.chunks.is_processed(chunk) {
+                continue;
+            }
+            let awake = self.chunks.is_awake(chunk);
+            let (mut restless, mut wet) = (false, false);
+            for (idx, local) in self.chunks.cells(chunk) {
+                let slot = chunk * cells_per_chunk + local;
+                let cell = &next[slot * stride..(slot + 1) * stride];
+                for s in 0..count {
+                    self.fluid[s][idx] = cell[s];
+                    wet |= cell[s] > 0f32;
+                    if awake {
+                        mass_out += outflow[slot * out_stride + s + 1] as f64;
+                    }
+                }
+                self.velocity[idx] = Vector2 { x: cell[count], y: cell[count + 1] };
+                mass_in += cell[count + 2] as f64;
+                restless |= cell[count + 3] > 0f32;
+            }
+            if restless {
+                self.chunks.mark_restless(chunk);
+            }
+            self.chunks.set_wet(chunk, wet);
+        }
+
+        self.outflow = outflow;
+        self.next = next;
+
+        let displaced = self.displace(adjacency);
+        self.chunks.settle();
+        (mass_in + displaced, mass_out + displaced)
+    }
+
+    /*
+     * Calls f with the index of every cell in the active chunks and that
+     * cell's stride wide slice of out, which is laid out chunk by chunk.
+     * Chunks run on the rayon thread pool when parallel flow is enabled
+     */
+    fn for_chunks<F: Fn(usize, &mut [f32]) + Sync>(&self, out: &mut [f32], stride: usize, active: &[bool], f: F) {
+        let run = |(chunk, cells): (usize, &mut [f32])| {
+            if !active[chunk] {
+                return;
+            }
+            for (idx, local) in self.chunks.cells(chunk) {
+                f(idx, &mut cells[local * stride..(local + 1) * stride]);
+            }
+        };
+        let chunk_size = self.chunks.cells_per_chunk() * stride;
+
+        #[cfg(feature = "rayon")]
+        {
+            if self.parallel {
+                out.par_chunks_mut(chunk_size).enumerate().for_each(run);
+                return;
+            }
+        }
+        out.chunks_mut(chunk_size).enumerate().for_each(run);
+    }
+
+    /*
+     * Fills cell with the new amount of every species in the cell at idx,
+     * followed by its new velocity, the amount of fluid it received and
+     * whether it is still changing enough to keep its chunk awake
+     */
+    fn gather(&self, adjacency: &Adjacency, outflow: &[f32], rate: f32, idx: usize, cell: &mut [f32]) {
+        let count = self.species.len();
+        let out_stride = count + 1;
+        let zero = Vector2 { x: 0f32, y: 0f32 };
+        let none = [0f32; 1];
+        let mut carried = zero;
+        let mut flux = zero;
+        let mut received = 0f32;
+        let mut change = 0f32;
+
+        let v = self.velocity[idx];
+        let slot = self.chunks.slot(idx);
+        let own = if self.chunks.is_awake(self.chunks.chunk_of(idx)) {
+            &outflow[slot * out_stride..(slot + 1) * out_stride]
+        } else {
+            &none
+        };
+        for (s, kept) in cell[..count].iter_mut().enumerate() {
+            *kept = self.fluid[s][idx] - own.get(s + 1).copied().unwrap_or(0f32);
+            let kept = *kept;
+            carried.x += kept * v.x;
+            carried.y += kept * v.y;
+        }
+        let sent: f32 = own[1..].iter().sum();
+        if sent > 0f32 {
+            for edge in adjacency.outgoing(idx) {
+                let amount = sent * self.flow_weight(adjacency, edge) / own[0];
+                let d = adjacency.direction(edge);
+                flux.x += amount * d.x;
+                flux.y += amount * d.y;
+            }
+        }
+
+        for edge in adjacency.incoming(idx) {
+            let from = adjacency.source(*edge);
+            for (s, species) in self.species.iter().enumerate() {
+                let difference = (self.fluid[s][from] - self.fluid[s][idx]).abs();
+                change = change.max(species.viscocity * rate * difference / adjacency.divisor());
+            }
+            if !self.chunks.is_awake(self.chunks.chunk_of(from)) {
+                continue;
+            }
+            let slot = self.chunks.slot(from);
+            let theirs = &outflow[slot * out_stride..(slot + 1) * out_stride];
+            if theirs[0] <= 0f32 {
+                continue;
+            }
+            let share = self.flow_weight(adjacency, *edge) / theirs[0];
+            let u = self.velocity[from];
+            let d = adjacency.direction(*edge);
+            for s in 0..count {
+                let amount = theirs[s + 1] * share;
+                cell[s] += amount;
+                received += amount;
+                carried.x += amount * u.x;
+                carried.y += amount * u.y;
+                flux.x += amount * d.x;
+                flux.y += amount * d.y;
+            }
+        }
+
+        for (s, fluid) in cell[..count].iter().enumerate() {
+            change = change.max((fluid - self.fluid[s][idx]).abs());
+        }
+        let total: f32 = cell[..count].iter().sum();
+        let velocity = if total > 0f32 {
+            let v = Vector2 {
+                x: (self.inertia * carried.x + (1f32 - self.inertia) * flux.x) / total,
+                y: (self.inertia * carried.y + (1f32 - self.inertia) * flux.y) / total,
+            };
+            let speed = (v.x * v.x + v.y * v.y).sqrt();
+            if speed > 1f32 {
+                Vector2 { x: v.x / speed, y: v.y / speed }
+            } else {
+                v
+            }
+        } else {
+            zero
+        };
+        cell[count] = velocity.x;
+        cell[count + 1] = velocity.y;
+        cell[count + 2] = received;
+        cell[count + 3] = if change > SLEEP_THRESHOLD * total.max(1f32) { 1f32 } else { 0f32 };
+    }
+
+    /*
+     * Open cells carry the weight of the fluid resting in them, while solid
+     * cells are pushed on by each of their open neighbours. Only chunks near
+     * fluid that moved can have changed
+     */
+    fn update_pressure(&mut self, adjacency: &Adjacency) {
+        for chunk in 0..self.chunks.count() {
+            if !self.chunks.is_touched(chunk) {
+                continue;
+            }
+            for (idx, _) in self.chunks.cells(chunk) {
+                if adjacency.is_open(idx) {
+                    self.pressure[idx] = self.cell_pressure(idx);
+                    self.force[idx] = Vector2 { x: 0f32, y: 0f32 };
+                    continue;
+                }
+
+                let mut pressure = 0f32;
+                let mut force = Vector2 { x: 0f32, y: 0f32 };
+                for edge in adjacency.outgoing(idx) {
+                    let push = self.cell_pressure(adjacency.target(edge)) * adjacency.weight(edge);
+                    let d = adjacency.direction(edge);
+                    pressure += push;
+                    force.x -= push * d.x;
+                    force.y -= push * d.y;
+                }
+                self.pressure[idx] = pressure;
+                self.force[idx] = force;
+            }
+        }
+    }
+
+    /*
+     * Each connection gets its neighbourhood weight, scaled up when it lies in
+     * the direction the fluid is already moving and down when against it. With
+     * temperature enabled, connections towards colder cells are favoured too
+     */
+    fn flow_weight(&self, adjacency: &Adjacency, edge: usize) -> f32 {
+        let from = adjacency.source(edge);
+        let v = self.velocity[from];
+        let d = adjacency.direction(edge);
+        let alignment = v.x * d.x + v.y * d.y;
+        let mut bias = 1f32 + VELOCITY_BIAS * alignment;
+        if let Some(temperature) = &self.temperature {
+            bias += temperature.buoyancy * (self.heat[from] - self.heat[adjacency.target(edge)]);
+        }
+        adjacency.weight(edge) * bias.max(MIN_BIAS)
+    }
+
+    /*
+     * Cells over capacity push their lightest species out to their
+     * connections, waking up the chunks the fluid lands in
+     */
+    fn displace(&mut self, adjacency: &Adjacency) -> f64 {
+        let mut displaced = 0f64;
+        if !self.capacity.is_finite() {
+            return displaced;
+        }
+
+        for chunk in 0..self.chunks.count() {
+            if !self.chunks.is_processed(chunk) {
+                continue;
+            }
+            for (idx, _) in self.chunks.cells(chunk) {
+                let total: f32 = self.fluid.iter().map(|f| f[idx]).sum();
+                let connections = adjacency.outgoing(idx);
+                if total <= self.capacity || !adjacency.is_open(idx) || connections.is_empty() {
+                    continue;
+                }
+                let mut excess = total - self.capacity;
+                for s in self.by_density.iter() {
+                    if excess <= 0f32 {
+                        break;
+                    }
+                    let moved = self.fluid[*s][idx].min(excess);
+                    self.fluid[*s][idx] -= moved;
+                    excess -= moved;
+                    displaced += moved as f64;
+                    let share = moved / connections.len() as f32;
+                    for edge in connections.clone() {
+                        self.fluid[*s][adjacency.target(edge)] += share;
+                    }
+                }
+                for edge in connections {
+                    let target = self.chunks.chunk_of(adjacency.target(edge));
+                    self.chunks.mark_restless(target);
+                }
+                self.chunks.mark_restless(chunk);
+            }
+        }
+        displaced
+    }
+
+    /*
+     * Species from lightest to heaviest, the order they are pushed out of a
+     * cell that is over capacity
+     */
+    fn sort_by_density(&mut self) {
+        let species = &self.species;
+        self.by_density = (0..species.len()).collect();
+        self.by_density.sort_by(|a, b| {
+            species[*a].density
+                .partial_cmp(&species[*b].density)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+
+    /*
+     * The velocity of every cell as rows of x,y pairs
+     */
+    pub fn velocity_text(&self) -> String {
+        let mut buf = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(v) = self.get_velocity([x, y]) {
+                    buf.push_str(&format!(" {:5.2},{:5.2} ", v.x, v.y));
+                }
+            }
+            buf.push('\n');
+        }
+        buf
+    }
+
+    /*
+     * Logs velocity_text as a debug event instead of writing to stdout
+     */
+    pub fn print_velocity(&self) {
+        debug!(tick = self.tick, "velocity\n{}", self.velocity_text());
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_save(&self) -> FluidSave {
+        let cells = self.width * self.height;
+        FluidSave {
+            version: SAVE_VERSION,
+            width: self.width,
+            height: self.height,
+            viscocity: None,
+            fluid: None,
+            species: Some(self.species.clone()),
+            species_fluid: Some(self.fluid.iter().map(|fluid| fluid[..cells].to_vec()).collect()),
+            pressure: self.pressure.clone(),
+            force: Some(self.force.iter().map(|f| [f.x, f.y]).collect()),
+            velocity: self.velocity.iter().map(|v| [v.x, v.y]).collect(),
+            simulation: Some(SimulationSave {
+                timestep: self.timestep,
+                substeps: self.substeps,
+                accumulator: self.accumulator,
+                capacity: Some(self.capacity).filter(|capacity| capacity.is_finite()),
+                tick: self.tick,
+                inertia: self.inertia,
+                next_source: self.next_source,
+                sources: self.sources.clone(),
+                chunk_size: self.chunks.size(),
+                awake: self.chunks.awake().to_vec(),
+            }),
+            temperature: self.temperature.map(|settings| TemperatureSave {
+                settings,
+                heat: self.heat.clone(),
+                sources: self.heat_sources.clone(),
+            }),
+        }
+    }
+
+    /*
+     * Restores a grid from a save of any supported version. Connections are
+     * not saved, they are looked up again on the first step
+     */
+    #[cfg(feature = "serde")]
+    pub fn from_save(save: FluidSave) -> Result<FluidGrid, SaveError> {
+        let save = save.migrate()?;
+        let cells = save.width.checked_mul(save.height).ok_or(SaveError::WrongSize("width"))?;
+        let species = save.species.ok_or(SaveError::Missing("species"))?;
+        let fluid = save.species_fluid.ok_or(SaveError::Missing("species_fluid"))?;
+        if species.is_empty() || fluid.len() != species.len() {
+            return Err(SaveError::WrongSize("species_fluid"));
+        }
+        if fluid.iter().any(|fluid| fluid.len() != cells) {
+            return Err(SaveError::WrongSize("species_fluid"));
+        }
+        if save.pressure.len() != cells {
+            return Err(SaveError::WrongSize("pressure"));
+        }
+        if save.velocity.len() != cells {
+            return Err(SaveError::WrongSize("velocity"));
+        }
+        if species.iter().any(|species| amount(species.viscocity).and(finite(species.density)).is_err()) {
+            return Err(SaveError::Invalid("species"));
+        }
+        if fluid.iter().flatten().any(|fluid| amount(*fluid).is_err()) {
+            return Err(SaveError::Invalid("species_fluid"));
+        }
+        if save.pressure.iter().chain(save.velocity.iter().flatten()).any(|value| !value.is_finite()) {
+            return Err(SaveError::Invalid("pressure"));
+        }
+        let vector = |v: &[f32; 2]| Vector2 { x: v[0], y: v[1] };
+
+        let mut grid = FluidGrid::with_species(save.width, save.height, species);
+        grid.fluid = fluid;
+        grid.pressure = save.pressure;
+        grid.velocity = save.velocity.iter().map(vector).collect();
+        if let Some(force) = save.force {
+            if force.len() != cells {
+                return Err(SaveError::WrongSize("force"));
+            }
+            if force.iter().flatten().any(|value| !value.is_finite()) {
+                return Err(SaveError::Invalid("force"));
+            }
+            grid.force = force.iter().map(vector).collect();
+        }
+        if let Some(temperature) = save.temperature {
+            let invalid = SaveError::Invalid("temperature");
+            grid.enable_temperature(temperature.settings).map_err(|_| invalid.clone())?;
+            if temperature.heat.len() != cells {
+                return Err(SaveError::WrongSize("temperature"));
+            }
+            if temperature.heat.iter().any(|t| !t.is_finite()) {
+                return Err(invalid);
+            }
+            let valid = |source: &HeatSource| {
+                grid.valid_position(&source.position) && finite(source.temperature).and(amount(source.rate)).is_ok()
+            };
+            if !temperature.sources.iter().all(|(_, source)| valid(source)) {
+                return Err(invalid);
+            }
+            grid.heat = temperature.heat;
+            grid.heat_sources = temperature.sources;
+        }
+        match save.simulation {
+            Some(simulation) => {
+                let invalid = SaveError::Invalid("simulation");
+                grid.set_timestep(simulation.timestep).map_err(|_| invalid.clone())?;
+                grid.set_capacity(simulation.capacity.unwrap_or(f32::INFINITY)).map_err(|_| invalid.clone())?;
+                grid.set_inertia(simulation.inertia).map_err(|_| invalid.clone())?;
+                if !simulation.accumulator.is_finite() {
+                    return Err(invalid);
+                }
+                if simulation.chunk_size == 0 || simulation.chunk_size > grid.width.max(grid.height).max(1) {
+                    return Err(invalid);
+                }
+                if simulation.sources.iter().any(|(_, source)| grid.check_source(source).is_err()) {
+                    return Err(SaveError::Invalid("sources"));
+                }
+                grid.substeps = simulation.substeps.max(1);
+                grid.accumulator = simulation.accumulator;
+                grid.tick = simulation.tick;
+                grid.next_source = simulation.next_source;
+                grid.sources = simulation.sources;
+                grid.chunks = Chunks::new(grid.width, grid.height, simulation.chunk_size);
+                grid.chunks.set_awake(&simulation.awake);
+            },
+            None => grid.chunks.wake_all(),
+        }
+        Ok(grid)
+    }
+
+    pub fn iter(&self) -> FluidIterator<'_> {
+        self.iter_species(SpeciesId::default())
+    }
+
+    pub fn iter_species(&self, species: SpeciesId) -> FluidIterator<'_> {
+        FluidIterator::new(self, species)
+    }
+
+    pub fn get_cell<T: Into<Point2<usize>>>(&self, point: T) -> Option<FluidCell> {
+        let point = point.into();
+        if !self.valid_position(&point) {
+            None
+        } else {
+            Some(self.cell(point))
+        }
+    }
+
+    fn cell(&self, position: Point2<usize>) -> FluidCell {
+        let index = self.index(&position);
+        FluidCell {
+            position,
+            fluid: self.total_at(index),
+            pressure: self.pressure[index],
+            velocity: self.velocity[index],
+        }
+    }
+
+    pub fn iter_cells(&self) -> CellIterator<'_> {
+        self.iter_rect(self.bounds())
+    }
+
+    /*
+     * The cells inside rect, clipped to the grid, row by row
+     */
+    pub fn iter_rect(&self, rect: Rect) -> CellIterator<'_> {
+        CellIterator {
+            grid: self,
+            cursor: Cursor::new(rect.intersection(&self.bounds()), self.width),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> CellIterMut<'_> {
+        self.iter_species_mut(SpeciesId::default())
+    }
+
+    /*
+     * Changes made through the cells are validated like the other mutators.
+     * Every chunk is woken up, those where nothing changed fall asleep again
+     * after the next step
+     */
+    pub fn iter_species_mut(&mut self, species: SpeciesId) -> CellIterMut<'_> {
+        self.chunks.wake_all();
+        let (fluid, velocity) = match self.fluid.get_mut(species.0) {
+            Some(fluid) => (fluid.as_mut_slice(), self.velocity.as_mut_slice()),
+            None => (&mut [][..], &mut [][..]),
+        };
+        CellIterMut {
+            width: self.width,
+            front: 0,
+            fluid,
+            velocity,
+            pressure: &self.pressure,
+            adjacency: &self.adjacency,
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_cells(&self) -> impl IndexedParallelIterator<Item = FluidCell> + '_ {
+        (0..self.width * self.height)
+            .into_par_iter()
+            .map(move |index| self.cell([index % self.width, index / self.width].into()))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_mut(&mut self) -> impl IndexedParallelIterator<Item = FluidCellMut<'_>> {
+        self.par_iter_species_mut(SpeciesId::default())
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_species_mut(&mut self, species: SpeciesId) -> impl IndexedParallelIterator<Item = FluidCellMut<'_>> {
+        self.chunks.wake_all();
+        let (fluid, velocity) = match self.fluid.get_mut(species.0) {
+            Some(fluid) => (fluid.as_mut_slice(), self.velocity.as_mut_slice()),
+            None => (&mut [][..], &mut [][..]),
+        };
+        let (width, pressure, adjacency) = (self.width, &self.pressure, &self.adjacency);
+        fluid
+            .par_iter_mut()
+            .zip(velocity.par_iter_mut())
+            .enumerate()
+            .map(move |(index, (fluid, velocity))| FluidCellMut {
+                position: [index % width, index / width].into(),
+                solid: adjacency.is_known_solid(index),
+                pressure: pressure[index],
+                fluid,
+                velocity,
+            })
+    }
+}
+
+/*
+ * Copies a row major buffer into one of a different size, cells that are in
+ * both keep their position
+ */
+fn remap<T: Copy>(data: &[T], from_width: usize, from_height: usize, width: usize, height: usize, empty: T) -> Vec<T> {
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            if x < from_width && y < from_height {
+                data[x + y * from_width]
+            } else {
+                empty
+            }
+        })
+        .collect()
+}
+
+impl std::fmt::Debug for FluidGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut buf = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(v) = self.get_total_fluid([x, y]) {
+                    buf.push_str(&format!(" {:06.3} ", v));
+                }
+            }
+            buf.push('\n');
+        }
+        writeln!(f, "{}", buf)
+    }
+}
+
+/*
+ * Walks the cells of a rect from either end
+ */
+#[derive(Clone, Debug)]
+struct Cursor {
+    rect: Rect,
+    width: usize,
+    front: usize,
+    back: usize,
+}
+
+impl Cursor {
+    fn new(rect: Rect, width: usize) -> Self {
+        Cursor {
+            rect,
+            width,
+            front: 0,
+            back: rect.area(),
+        }
+    }
+
+    fn at(&self, i: usize) -> (Point2<usize>, usize) {
+        let (x, y) = (self.rect.x + i % self.rect.width, self.rect.y + i / self.rect.width);
+        ([x, y].into(), x + y * self.width)
+    }
+
+    fn next(&mut self) -> Option<(Point2<usize>, usize)> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.at(self.front - 1))
+    }
+
+    fn next_back(&mut self) -> Option<(Point2<usize>, usize)> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.at(self.back))
+    }
+
+    fn len(&self) -> usize {
+        self.back - self.front
+    }
+}
+
+pub struct FluidIterator<'a> {
+    fluid: &'a [f32],
+    cursor: Cursor,
+}
+
+impl<'a> FluidIterator<'a> {
+    fn new(fluid_grid: &'a FluidGrid, species: SpeciesId) -> Self {
+        match fluid_grid.fluid.get(species.0) {
+            Some(fluid) => FluidIterator {
+                fluid,
+                cursor: Cursor::new(fluid_grid.bounds(), fluid_grid.width),
+            },
+            None => FluidIterator {
+                fluid: &[],
+                cursor: Cursor::new(Rect::default(), fluid_grid.width),
+            },
+        }
+    }
+}
+
+impl<'a> Iterator for FluidIterator<'a> {
+    type Item = (Point2<usize>, f32);
+
+    fn next(&mut self) -> Option<(Point2<usize>, f32)> {
+        let (position, index) = self.cursor.next()?;
+        Some((position, self.fluid[index]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.len(), Some(self.cursor.len()))
+    }
+}
+
+impl<'a> DoubleEndedIterator for FluidIterator<'a> {
+    fn next_back(&mut self) -> Option<(Point2<usize>, f32)> {
+        let (position, index) = self.cursor.next_back()?;
+        Some((position, self.fluid[index]))
+    }
+}
+
+impl<'a> ExactSizeIterator for FluidIterator<'a> {}
+
+impl<'a> FusedIterator for FluidIterator<'a> {}
+
+/*
+ * A copy of a cell, the fluid is the total of all species
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FluidCell {
+    pub position: Point2<usize>,
+    pub fluid: f32,
+    pub pressure: f32,
+    pub velocity: Vector2<f32>,
+}
+
+pub struct CellIterator<'a> {
+    grid: &'a FluidGrid,
+    cursor: Cursor,
+}
+
+impl<'a> Iterator for CellIterator<'a> {
+    type Item = FluidCell;
+
+    fn next(&mut self) -> Option<FluidCell> {
+        let (position, _) = self.cursor.next()?;
+        Some(self.grid.cell(position))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.len(), Some(self.cursor.len()))
+    }
+}
+
+impl<'a> DoubleEndedIterator for CellIterator<'a> {
+    fn next_back(&mut self) -> Option<FluidCell> {
+        let (position, _) = self.cursor.next_back()?;
+        Some(self.grid.cell(position))
+    }
+}
+
+impl<'a> ExactSizeIterator for CellIterator<'a> {}
+
+impl<'a> FusedIterator for CellIterator<'a> {}
+
+/*
+ * A cell that can be changed in place, for a single species. Pressure is
+ * read only, it follows from the fluid on the next step
+ */
+pub struct FluidCellMut<'a> {
+    position: Point2<usize>,
+    solid: bool,
+    pressure: f32,
+    fluid: &'a mut f32,
+    velocity: &'a mut Vector2<f32>,
+}
+
+impl<'a> FluidCellMut<'a> {
+    pub fn position(&self) -> Point2<usize> {
+        self.position
+    }
+
+    pub fn fluid(&self) -> f32 {
+        *self.fluid
+    }
+
+    pub fn pressure(&self) -> f32 {
+        self.pressure
+    }
+
+    pub fn velocity(&self) -> Vector2<f32> {
+        *self.velocity
+    }
+
+    pub fn set_fluid(&mut self, value: f32) -> Result<f32, FluidError> {
+        amount(value)?;
+        if self.solid {
+            return Err(FluidError::CellSolid(self.position));
+        }
+        *self.fluid = value;
+        Ok(value)
+    }
+
+    pub fn add_fluid(&mut self, value: f32) -> Result<f32, FluidError> {
+        finite(value)?;
+        self.set_fluid(*self.fluid + value)
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vector2<f32>) -> Result<(), FluidError> {
+        finite(velocity.x)?;
+        finite(velocity.y)?;
+        *self.velocity = velocity;
+        Ok(())
+    }
+}
+
+pub struct CellIterMut<'a> {
+    width: usize,
+    front: usize,
+    fluid: &'a mut [f32],
+    velocity: &'a mut [Vector2<f32>],
+    pressure: &'a [f32],
+    adjacency: &'a Adjacency,
+}
+
+impl<'a> CellIterMut<'a> {
+    fn cell(&self, index: usize, fluid: &'a mut f32, velocity: &'a mut Vector2<f32>) -> FluidCellMut<'a> {
+        FluidCellMut {
+            position: [index % self.width, index / self.width].into(),
+            solid: self.adjacency.is_known_solid(index),
+            pressure: self.pressure[index],
+            fluid,
+            velocity,
+        }
+    }
+}
+
+impl<'a> Iterator for CellIterMut<'a> {
+    type Item = FluidCellMut<'a>;
+
+    fn next(&mut self) -> Option<FluidCellMut<'a>> {
+        let (fluid, rest) = std::mem::take(&mut self.fluid).split_first_mut()?;
+        self.fluid = rest;
+        let (velocity, rest) = std::mem::take(&mut self.velocity).split_first_mut()?;
+        self.velocity = rest;
+        self.front += 1;
+        Some(self.cell(self.front - 1, fluid, velocity))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.fluid.len(), Some(self.fluid.len()))
+    }
+}
+
+impl<'a> DoubleEndedIterator for CellIterMut<'a> {
+    fn next_back(&mut self) -> Option<FluidCellMut<'a>> {
+        let (fluid, rest) = std::mem::take(&mut self.fluid).split_last_mut()?;
+        self.fluid = rest;
+        let (velocity, rest) = std::mem::take(&mut self.velocity).split_last_mut()?;
+        self.velocity = rest;
+        Some(self.cell(self.front + self.fluid.len(), fluid, velocity))
+    }
+}
+
+impl<'a> ExactSizeIterator for CellIterMut<'a> {}
+
+impl<'a> FusedIterator for CellIterMut<'a> {}
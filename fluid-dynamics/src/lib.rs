@@ -1,5 +1,33 @@
 mod fluid_grid;
+mod adjacency;
+mod chunks;
 mod connection_grid;
+mod neighbourhood;
+mod solid_grid;
+mod species;
+mod source;
+mod flow_report;
+mod rect;
+mod error;
+mod temperature;
+mod reaction;
+mod render;
+#[cfg(feature = "serde")]
+mod save;
 
-pub use crate::fluid_grid::{FluidGrid};
+pub use crate::fluid_grid::{CellIterator, CellIterMut, FluidCell, FluidCellMut, FluidGrid, FluidIterator};
 pub use crate::connection_grid::{ConnectionGrid};
+pub use crate::neighbourhood::{Neighbourhood, Offset};
+pub use crate::solid_grid::{SolidGrid};
+pub use crate::species::{Species, SpeciesId};
+pub use crate::source::{Source, SourceHandle, SourceKind};
+pub use crate::flow_report::{FlowReport};
+pub use crate::rect::{Rect};
+pub use crate::error::{FluidError};
+pub use crate::temperature::{HeatSource, Temperature};
+pub use crate::reaction::{Reaction, ReactionEvent, ReactionId, ReactionTable};
+pub use crate::render::{ColourRamp, Field, Frame, HeatMap};
+#[cfg(feature = "gif")]
+pub use crate::render::{write_gif};
+#[cfg(feature = "serde")]
+pub use crate::save::{FluidSave, SaveError, SimulationSave, TemperatureSave, SAVE_VERSION};
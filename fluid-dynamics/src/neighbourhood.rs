@@ -0,0 +1,119 @@
+use mint::{Point2};
+
+use crate::{FluidError};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Offset {
+    pub dx: isize,
+    pub dy: isize,
+    pub weight: f32,
+}
+
+impl Offset {
+    pub fn new(dx: isize, dy: isize, weight: f32) -> Self {
+        Offset {
+            dx,
+            dy,
+            weight
+        }
+    }
+
+    /*
+     * An offset moves fluid only if it leads to another cell with a positive,
+     * finite weight
+     */
+    pub fn is_valid(&self) -> bool {
+        (self.dx, self.dy) != (0, 0) && self.weight.is_finite() && self.weight > 0f32
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Neighbourhood {
+    #[default]
+    VonNeumann,
+    Moore,
+    /*
+     * Pointy topped hexagons where every odd row is shifted half a cell right
+     */
+    HexOffset,
+    /*
+     * Build with Neighbourhood::stencil to have the offsets checked, invalid
+     * offsets in a stencil made directly are left out
+     */
+    Stencil(Vec<Offset>),
+}
+
+const DIAGONAL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+const VON_NEUMANN: [(isize, isize); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+const DIAGONALS: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+const HEX_EVEN: [(isize, isize); 6] = [(-1, 0), (-1, -1), (0, -1), (1, 0), (0, 1), (-1, 1)];
+const HEX_ODD: [(isize, isize); 6] = [(-1, 0), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1)];
+
+impl Neighbourhood {
+    pub fn stencil(offsets: Vec<Offset>) -> Result<Self, FluidError> {
+        match offsets.iter().find(|offset| !offset.is_valid()) {
+            Some(offset) => Err(FluidError::InvalidOffset(*offset)),
+            None => Ok(Neighbourhood::Stencil(offsets)),
+        }
+    }
+
+    pub fn offsets<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Offset> {
+        let unit = |(dx, dy): &(isize, isize)| Offset::new(*dx, *dy, 1f32);
+        match self {
+            Neighbourhood::VonNeumann => VON_NEUMANN.iter().map(unit).collect(),
+            Neighbourhood::Moore => VON_NEUMANN
+                .iter()
+                .map(unit)
+                .chain(DIAGONALS.iter().map(|(dx, dy)| Offset::new(*dx, *dy, DIAGONAL)))
+                .collect(),
+            Neighbourhood::HexOffset => {
+                if pos.into().y % 2 == 0 {
+                    HEX_EVEN.iter().map(unit).collect()
+                } else {
+                    HEX_ODD.iter().map(unit).collect()
+                }
+            },
+            Neighbourhood::Stencil(offsets) => offsets.iter().copied().filter(Offset::is_valid).collect(),
+        }
+    }
+
+    /*
+     * All neighbours of a position that lie inside a width x height grid
+     */
+    pub fn neighbours<T: Into<Point2<usize>>>(&self, pos: T, width: usize, height: usize) -> Vec<(Point2<usize>, f32)> {
+        let pos = pos.into();
+        self.offsets(pos)
+            .iter()
+            .filter_map(|offset| {
+                let x = pos.x as isize + offset.dx;
+                let y = pos.y as isize + offset.dy;
+                if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+                    None
+                } else {
+                    Some(([x as usize, y as usize].into(), offset.weight))
+                }
+            })
+            .collect()
+    }
+
+    pub fn weight<T: Into<Point2<usize>>>(&self, from: T, to: T) -> Option<f32> {
+        let (from, to) = (from.into(), to.into());
+        let (dx, dy) = (to.x as isize - from.x as isize, to.y as isize - from.y as isize);
+        self.offsets(from)
+            .iter()
+            .find(|offset| offset.dx == dx && offset.dy == dy)
+            .map(|offset| offset.weight)
+    }
+
+    pub fn size(&self) -> usize {
+        self.offsets([0, 0]).len()
+    }
+
+    pub fn total_weight(&self) -> f32 {
+        self.offsets([0, 0])
+            .iter()
+            .map(|offset| offset.weight)
+            .sum()
+    }
+}
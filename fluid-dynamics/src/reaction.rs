@@ -0,0 +1,177 @@
+use mint::{Point2};
+use tracing::{debug, debug_span};
+
+use crate::{ConnectionGrid, FluidError, FluidGrid, SpeciesId};
+use crate::error::{amount, finite};
+
+/*
+ * Reactions smaller than this in a cell are skipped, so a reaction that has
+ * used up its inputs stops sending events
+ */
+pub(crate) const MIN_EXTENT: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ReactionId(pub(crate) usize);
+
+impl ReactionId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/*
+ * Turns inputs into outputs in the given ratios. Rate is the share of what
+ * could react that does so per unit of time. Heat is added to the temperature
+ * of the cell, and heat, pressure and damage are reported in the events, all
+ * per unit of reaction. Surface reactions only happen next to solid cells
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reaction {
+    pub inputs: Vec<(SpeciesId, f32)>,
+    pub outputs: Vec<(SpeciesId, f32)>,
+    pub rate: f32,
+    pub min_temperature: Option<f32>,
+    pub surface: bool,
+    pub heat: f32,
+    pub pressure: f32,
+    pub damage: f32,
+}
+
+impl Reaction {
+    pub fn new(inputs: Vec<(SpeciesId, f32)>, outputs: Vec<(SpeciesId, f32)>, rate: f32) -> Self {
+        Reaction {
+            inputs,
+            outputs,
+            rate,
+            min_temperature: None,
+            surface: false,
+            heat: 0f32,
+            pressure: 0f32,
+            damage: 0f32,
+        }
+    }
+
+    pub fn above(self, temperature: f32) -> Self {
+        Reaction {
+            min_temperature: Some(temperature),
+            ..self
+        }
+    }
+
+    pub fn on_surface(self) -> Self {
+        Reaction {
+            surface: true,
+            ..self
+        }
+    }
+
+    pub fn with_heat(self, heat: f32) -> Self {
+        Reaction {
+            heat,
+            ..self
+        }
+    }
+
+    pub fn with_pressure(self, pressure: f32) -> Self {
+        Reaction {
+            pressure,
+            ..self
+        }
+    }
+
+    pub fn with_damage(self, damage: f32) -> Self {
+        Reaction {
+            damage,
+            ..self
+        }
+    }
+
+    /*
+     * How much reacts in a cell during dt, given a lookup for the fluid of each
+     * species and the temperature of the cell if the grid has one
+     */
+    pub(crate) fn extent(&self, fluid: impl Fn(SpeciesId) -> f32, temperature: Option<f32>, dt: f32) -> f32 {
+        if let Some(min_temperature) = self.min_temperature {
+            if temperature.is_none_or(|t| t < min_temperature) {
+                return 0f32;
+            }
+        }
+        let possible = self.inputs
+            .iter()
+            .map(|(species, ratio)| fluid(*species) / ratio)
+            .fold(f32::INFINITY, f32::min);
+        if !possible.is_finite() {
+            return 0f32;
+        }
+        possible * (self.rate * dt).min(1f32)
+    }
+}
+
+/*
+ * What happened in a single cell during one call to react. Surface reactions
+ * send one event for every solid cell they touch, with the amounts split
+ * between them
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReactionEvent {
+    pub reaction: ReactionId,
+    pub position: Point2<usize>,
+    pub surface: Option<Point2<usize>>,
+    pub amount: f32,
+    pub heat: f32,
+    pub pressure: f32,
+    pub damage: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReactionTable {
+    reactions: Vec<Reaction>,
+}
+
+impl ReactionTable {
+    pub fn new() -> Self {
+        ReactionTable::default()
+    }
+
+    /*
+     * Reactions without inputs would create fluid out of nothing, they never
+     * happen
+     */
+    pub fn add(&mut self, reaction: Reaction) -> Result<ReactionId, FluidError> {
+        for (_, ratio) in reaction.inputs.iter().chain(reaction.outputs.iter()) {
+            amount(*ratio)?;
+        }
+        amount(reaction.rate)?;
+        if let Some(temperature) = reaction.min_temperature {
+            finite(temperature)?;
+        }
+        finite(reaction.heat)?;
+        finite(reaction.pressure)?;
+        finite(reaction.damage)?;
+        self.reactions.push(reaction);
+        Ok(ReactionId(self.reactions.len() - 1))
+    }
+
+    pub fn get(&self, reaction: ReactionId) -> Option<&Reaction> {
+        self.reactions.get(reaction.0)
+    }
+
+    pub fn reactions(&self) -> impl Iterator<Item = (ReactionId, &Reaction)> {
+        self.reactions
+            .iter()
+            .enumerate()
+            .map(|(i, reaction)| (ReactionId(i), reaction))
+    }
+
+    /*
+     * Runs every reaction in every open cell of grid for dt, in the order they
+     * were added. Reactions using species the grid does not have are skipped,
+     * as are dry chunks that have fallen asleep
+     */
+    pub fn react(&self, grid: &mut FluidGrid, connection_grid: &impl ConnectionGrid, dt: f32) -> Vec<ReactionEvent> {
+        let _span = debug_span!("react", reactions = self.reactions.len()).entered();
+        let events = grid.apply_reactions(self, connection_grid, dt);
+        debug!(events = events.len(), "reactions");
+        events
+    }
+}
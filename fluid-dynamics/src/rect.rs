@@ -0,0 +1,59 @@
+use mint::{Point2};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height
+        }
+    }
+
+    pub fn contains<T: Into<Point2<usize>>>(&self, point: T) -> bool {
+        let point = point.into();
+        point.x >= self.x && point.y >= self.y
+            && point.x - self.x < self.width && point.y - self.y < self.height
+    }
+
+    /*
+     * The part covered by both, empty rects keep the position of self. Rects
+     * reaching past usize::MAX are cut off there
+     */
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.x.saturating_add(self.width).min(other.x.saturating_add(other.width));
+        let bottom = self.y.saturating_add(self.height).min(other.y.saturating_add(other.height));
+        if right <= x || bottom <= y {
+            Rect::new(self.x, self.y, 0, 0)
+        } else {
+            Rect::new(x, y, right - x, bottom - y)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    /*
+     * Every point inside, row by row
+     */
+    pub fn points(&self) -> impl Iterator<Item = Point2<usize>> {
+        let Rect { x, y, width, height } = *self;
+        (0..width * height).map(move |i| [x + i % width, y + i / width].into())
+    }
+}
@@ -0,0 +1,298 @@
+use mint::{Point2};
+use std::io::{self, Write};
+
+use crate::{ConnectionGrid, FluidGrid, SpeciesId};
+
+/*
+ * The value of a cell a heat map shows, velocity is drawn by its speed
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Fluid,
+    Species(SpeciesId),
+    Pressure,
+    Velocity,
+    Temperature,
+}
+
+/*
+ * Maps values from 0.0 to 1.0 onto colours, blending linearly between the
+ * stops. Stops outside of that range are clamped into it and ones that are
+ * not a number are dropped
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColourRamp {
+    stops: Vec<(f32, [u8; 3])>,
+}
+
+impl ColourRamp {
+    pub fn new(stops: Vec<(f32, [u8; 3])>) -> Self {
+        let mut stops = stops
+            .into_iter()
+            .filter(|(at, _)| !at.is_nan())
+            .map(|(at, colour)| (at.clamp(0f32, 1f32), colour))
+            .collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        ColourRamp {
+            stops,
+        }
+    }
+
+    pub fn evenly(colours: &[[u8; 3]]) -> Self {
+        let last = colours.len().saturating_sub(1).max(1) as f32;
+        ColourRamp::new(colours
+            .iter()
+            .enumerate()
+            .map(|(i, colour)| (i as f32 / last, *colour))
+            .collect())
+    }
+
+    /*
+     * Black through red and yellow to white
+     */
+    pub fn heat() -> Self {
+        ColourRamp::evenly(&[[0, 0, 0], [255, 0, 0], [255, 255, 0], [255, 255, 255]])
+    }
+
+    pub fn grey() -> Self {
+        ColourRamp::evenly(&[[0, 0, 0], [255, 255, 255]])
+    }
+
+    /*
+     * Dark blue through green to pale yellow, readable for colour blind eyes
+     */
+    pub fn ocean() -> Self {
+        ColourRamp::evenly(&[[8, 16, 64], [32, 96, 160], [64, 176, 120], [240, 240, 160]])
+    }
+
+    pub fn stops(&self) -> &[(f32, [u8; 3])] {
+        &self.stops
+    }
+
+    pub fn sample(&self, value: f32) -> [u8; 3] {
+        let value = if value.is_nan() { 0f32 } else { value.clamp(0f32, 1f32) };
+        let upper = match self.stops.iter().position(|(at, _)| *at >= value) {
+            Some(0) => return self.stops[0].1,
+            Some(upper) => upper,
+            None => return self.stops.last().map_or([0, 0, 0], |(_, colour)| *colour),
+        };
+        let ((from, low), (to, high)) = (self.stops[upper - 1], self.stops[upper]);
+        let t = if to > from { (value - from) / (to - from) } else { 1f32 };
+        let mut colour = [0u8; 3];
+        for (c, (low, high)) in colour.iter_mut().zip(low.iter().zip(high.iter())) {
+            *c = (*low as f32 + (*high as f32 - *low as f32) * t).round() as u8;
+        }
+        colour
+    }
+}
+
+impl Default for ColourRamp {
+    fn default() -> Self {
+        ColourRamp::heat()
+    }
+}
+
+/*
+ * An RGB image with three bytes per pixel, row by row from the top left
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 3]> {
+        if x < self.width && y < self.height {
+            let i = (x + y * self.width) * 3;
+            Some([self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]])
+        } else {
+            None
+        }
+    }
+
+    /*
+     * Binary PPM, which most image tools read and needs no dependencies
+     */
+    pub fn write_ppm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)?;
+        writer.flush()
+    }
+
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&self.pixels).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+/*
+ * Writes frames of the same size as a looping GIF, showing each for delay
+ * hundredths of a second. Colours are reduced to a palette per frame
+ */
+#[cfg(feature = "gif")]
+pub fn write_gif<W: Write>(frames: &[Frame], delay: u16, writer: W) -> io::Result<()> {
+    let (width, height) = frames.first().map_or((0, 0), |frame| (frame.width, frame.height));
+    if frames.iter().any(|frame| frame.width != width || frame.height != height) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "frames differ in size"));
+    }
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "frames are too large for a GIF"));
+    }
+    let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &[]).map_err(io::Error::other)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+    for frame in frames {
+        let mut gif_frame = gif::Frame::from_rgb_speed(width as u16, height as u16, &frame.pixels, 10);
+        gif_frame.delay = delay;
+        encoder.write_frame(&gif_frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+/*
+ * Draws a field of a grid with every cell as a square of scale pixels. The
+ * range of values spread over the ramp is taken from each grid unless set,
+ * set it when rendering several frames of an animation so they match. Scale
+ * is only set through with_scale, which keeps it at one pixel or more
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeatMap {
+    pub field: Field,
+    pub ramp: ColourRamp,
+    pub range: Option<(f32, f32)>,
+    scale: usize,
+    pub wall_colour: [u8; 3],
+}
+
+impl HeatMap {
+    pub fn new(field: Field) -> Self {
+        HeatMap {
+            field,
+            ramp: ColourRamp::default(),
+            range: None,
+            scale: 1,
+            wall_colour: [64, 64, 64],
+        }
+    }
+
+    pub fn with_ramp(self, ramp: ColourRamp) -> Self {
+        HeatMap {
+            ramp,
+            ..self
+        }
+    }
+
+    pub fn with_range(self, min: f32, max: f32) -> Self {
+        HeatMap {
+            range: Some((min, max)),
+            ..self
+        }
+    }
+
+    pub fn with_scale(self, scale: usize) -> Self {
+        HeatMap {
+            scale: scale.max(1),
+            ..self
+        }
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    pub fn with_wall_colour(self, wall_colour: [u8; 3]) -> Self {
+        HeatMap {
+            wall_colour,
+            ..self
+        }
+    }
+
+    fn value(&self, grid: &FluidGrid, pos: Point2<usize>) -> f32 {
+        match self.field {
+            Field::Fluid => grid.get_total_fluid(pos).unwrap_or(0f32),
+            Field::Species(species) => grid.get_species_fluid(species, pos).copied().unwrap_or(0f32),
+            Field::Pressure => grid.get_pressure(pos).copied().unwrap_or(0f32),
+            Field::Velocity => grid.get_velocity(pos).map_or(0f32, |v| (v.x * v.x + v.y * v.y).sqrt()),
+            Field::Temperature => grid.get_temperature(pos).unwrap_or(0f32),
+        }
+    }
+
+    /*
+     * The range set on the heat map, or else from the lowest to the highest
+     * value in grid. Amounts and speeds always start at 0.0
+     */
+    pub fn range(&self, grid: &FluidGrid) -> (f32, f32) {
+        if let Some(range) = self.range {
+            return range;
+        }
+        let start = match self.field {
+            Field::Pressure | Field::Temperature => f32::INFINITY,
+            _ => 0f32,
+        };
+        grid.bounds()
+            .points()
+            .map(|pos| self.value(grid, pos))
+            .filter(|value| value.is_finite())
+            .fold((start, f32::NEG_INFINITY), |(min, max), value| (min.min(value), max.max(value)))
+    }
+
+    fn draw(&self, grid: &FluidGrid, is_solid: impl Fn(Point2<usize>) -> bool) -> Frame {
+        let size = grid.dimensions();
+        let (min, max) = self.range(grid);
+        let cells = grid.bounds()
+            .points()
+            .map(|pos| {
+                if is_solid(pos) {
+                    self.wall_colour
+                } else if max > min {
+                    self.ramp.sample((self.value(grid, pos) - min) / (max - min))
+                } else {
+                    self.ramp.sample(0f32)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let (width, height) = (size.x * self.scale, size.y * self.scale);
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&cells[x / self.scale + y / self.scale * size.x]);
+            }
+        }
+        Frame {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn render(&self, grid: &FluidGrid) -> Frame {
+        self.draw(grid, |_| false)
+    }
+
+    /*
+     * Like render, with the solid cells of connection_grid drawn in the wall
+     * colour on top
+     */
+    pub fn render_with_walls(&self, grid: &FluidGrid, connection_grid: &impl ConnectionGrid) -> Frame {
+        self.draw(grid, |pos| connection_grid.is_solid(pos))
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+use crate::{FluidGrid, HeatSource, Source, SourceHandle, Species, Temperature};
+
+pub const SAVE_VERSION: u32 = 2;
+
+/*
+ * The saved state of a FluidGrid. Version 1 is the layout from before species
+ * existed, with a single fluid and viscocity, and is migrated on load. Fields
+ * that one of the versions does not have are optional
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FluidSave {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub viscocity: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fluid: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub species: Option<Vec<Species>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub species_fluid: Option<Vec<Vec<f32>>>,
+    pub pressure: Vec<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force: Option<Vec<[f32; 2]>>,
+    pub velocity: Vec<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub simulation: Option<SimulationSave>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<TemperatureSave>,
+}
+
+/*
+ * Everything about how the grid steps, which version 1 saves did not keep
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SimulationSave {
+    pub timestep: f32,
+    pub substeps: u32,
+    pub accumulator: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<f32>,
+    pub tick: u64,
+    pub inertia: f32,
+    pub next_source: u64,
+    pub sources: Vec<(SourceHandle, Source)>,
+    pub chunk_size: usize,
+    pub awake: Vec<bool>,
+}
+
+/*
+ * The temperature field, only present for grids that have it enabled
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TemperatureSave {
+    pub settings: Temperature,
+    pub heat: Vec<f32>,
+    pub sources: Vec<(SourceHandle, HeatSource)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SaveError {
+    UnsupportedVersion(u32),
+    Missing(&'static str),
+    WrongSize(&'static str),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::UnsupportedVersion(version) => write!(f, "unsupported save version {}", version),
+            SaveError::Missing(field) => write!(f, "save is missing {}", field),
+            SaveError::WrongSize(field) => write!(f, "{} does not match the size of the grid", field),
+            SaveError::Invalid(field) => write!(f, "{} holds values that are negative or not finite", field),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl FluidSave {
+    /*
+     * Brings a save of any known version up to the current one
+     */
+    pub fn migrate(self) -> Result<FluidSave, SaveError> {
+        match self.version {
+            1 => {
+                let fluid = self.fluid.ok_or(SaveError::Missing("fluid"))?;
+                let species = Species {
+                    viscocity: self.viscocity.unwrap_or(1f32),
+                    ..Species::default()
+                };
+                FluidSave {
+                    version: 2,
+                    viscocity: None,
+                    fluid: None,
+                    species: Some(vec![species]),
+                    species_fluid: Some(vec![fluid]),
+                    ..self
+                }.migrate()
+            },
+            SAVE_VERSION => Ok(self),
+            version => Err(SaveError::UnsupportedVersion(version)),
+        }
+    }
+}
+
+impl Serialize for FluidGrid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_save().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FluidGrid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FluidGrid, D::Error> {
+        let save = FluidSave::deserialize(deserializer)?;
+        FluidGrid::from_save(save).map_err(serde::de::Error::custom)
+    }
+}
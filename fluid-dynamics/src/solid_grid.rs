@@ -0,0 +1,162 @@
+use mint::{Point2, Vector2};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::{ConnectionGrid, Neighbourhood};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolidGrid {
+    width: usize,
+    height: usize,
+    solid: Vec<bool>,
+    conductance: HashMap<(usize, usize), f32>,
+    neighbourhood: Neighbourhood,
+}
+
+impl SolidGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        SolidGrid {
+            width,
+            height,
+            solid: vec![false; width * height],
+            conductance: HashMap::new(),
+            neighbourhood: Neighbourhood::default(),
+        }
+    }
+
+    pub fn from_fn<F: Fn(Point2<usize>) -> bool>(width: usize, height: usize, is_solid: F) -> Self {
+        let solid = (0..width * height)
+            .map(|i| is_solid([i % width, i / width].into()))
+            .collect();
+        SolidGrid {
+            width,
+            height,
+            solid,
+            conductance: HashMap::new(),
+            neighbourhood: Neighbourhood::default(),
+        }
+    }
+
+    pub fn with_neighbourhood(self, neighbourhood: Neighbourhood) -> Self {
+        SolidGrid {
+            neighbourhood,
+            ..self
+        }
+    }
+
+    pub fn dimensions(&self) -> Vector2<usize> {
+        [self.width, self.height].into()
+    }
+
+    pub fn set_solid<T: Into<Point2<usize>>>(&mut self, pos: T, solid: bool) {
+        let pos = pos.into();
+        if pos.x < self.width && pos.y < self.height {
+            self.solid[pos.x + pos.y * self.width] = solid;
+        }
+    }
+
+    fn index(&self, pos: Point2<usize>) -> Option<usize> {
+        if pos.x < self.width && pos.y < self.height {
+            Some(pos.x + pos.y * self.width)
+        } else {
+            None
+        }
+    }
+
+    fn set_directed<T: Into<Point2<usize>>>(&mut self, from: T, to: T, conductance: f32) {
+        if let (Some(from), Some(to)) = (self.index(from.into()), self.index(to.into())) {
+            let conductance = if conductance.is_nan() { 0f32 } else { conductance.clamp(0f32, 1f32) };
+            if conductance == 1f32 {
+                self.conductance.remove(&(from, to));
+            } else {
+                self.conductance.insert((from, to), conductance);
+            }
+        }
+    }
+
+    /*
+     * Throttles the connection between two neighbouring cells in both
+     * directions, 0.0 closes it and 1.0 opens it fully again
+     */
+    pub fn set_conductance<T: Into<Point2<usize>>>(&mut self, from: T, to: T, conductance: f32) {
+        let (from, to) = (from.into(), to.into());
+        self.set_directed(from, to, conductance);
+        self.set_directed(to, from, conductance);
+    }
+
+    /*
+     * Closes the connection from to back to from, leaving the other direction
+     * as it was
+     */
+    pub fn set_one_way<T: Into<Point2<usize>>>(&mut self, from: T, to: T) {
+        self.set_directed(to, from, 0f32);
+    }
+}
+
+/*
+ * Rows are separated by newlines, '#' or '1' is solid and '.' or '0' is open.
+ * Leading and trailing whitespace on each row is ignored
+ */
+impl FromStr for SolidGrid {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<SolidGrid, ()> {
+        let rows = input
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let width = rows.first().map(|row| row.chars().count()).unwrap_or(0);
+        let mut solid = Vec::with_capacity(width * rows.len());
+        for row in rows.iter() {
+            if row.chars().count() != width {
+                return Err(());
+            }
+            for c in row.chars() {
+                match c {
+                    '#' | '1' => solid.push(true),
+                    '.' | '0' => solid.push(false),
+                    _ => return Err(())
+                }
+            }
+        }
+        Ok(SolidGrid {
+            width,
+            height: rows.len(),
+            solid,
+            conductance: HashMap::new(),
+            neighbourhood: Neighbourhood::default(),
+        })
+    }
+}
+
+impl ConnectionGrid for SolidGrid {
+    fn get_connections<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Point2<usize>> {
+        let from = pos.into();
+        self.neighbourhood
+            .neighbours(from, self.width, self.height)
+            .into_iter()
+            .map(|(pos, _)| pos)
+            .filter(|to| !self.is_solid(*to) && self.conductance(from, *to) > 0f32)
+            .collect()
+    }
+
+    fn is_solid<T: Into<Point2<usize>>>(&self, pos: T) -> bool {
+        let pos = pos.into();
+        if pos.x >= self.width || pos.y >= self.height {
+            return true;
+        }
+        self.solid[pos.x + pos.y * self.width]
+    }
+
+    fn conductance<T: Into<Point2<usize>>>(&self, from: T, to: T) -> f32 {
+        match (self.index(from.into()), self.index(to.into())) {
+            (Some(from), Some(to)) => self.conductance.get(&(from, to)).copied().unwrap_or(1f32),
+            _ => 0f32,
+        }
+    }
+
+    fn neighbourhood(&self) -> &Neighbourhood {
+        &self.neighbourhood
+    }
+}
@@ -0,0 +1,95 @@
+use mint::{Point2};
+
+use crate::{SpeciesId};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceHandle(pub(crate) u64);
+
+/*
+ * Rates are per unit of time, so they are scaled by the timestep of the grid
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SourceKind {
+    Constant { rate: f32 },
+    Pulsed { amount: f32, interval: u32 },
+    Reservoir { rate: f32, remaining: f32 },
+    PressureLimited { rate: f32, limit: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Source {
+    pub position: Point2<usize>,
+    pub species: SpeciesId,
+    pub kind: SourceKind,
+    pub drain: bool,
+    pub paused: bool,
+}
+
+impl Source {
+    pub fn emitter<T: Into<Point2<usize>>>(position: T, kind: SourceKind) -> Self {
+        Source {
+            position: position.into(),
+            species: SpeciesId::default(),
+            kind,
+            drain: false,
+            paused: false,
+        }
+    }
+
+    pub fn drain<T: Into<Point2<usize>>>(position: T, kind: SourceKind) -> Self {
+        Source {
+            drain: true,
+            ..Source::emitter(position, kind)
+        }
+    }
+
+    pub fn with_species(self, species: SpeciesId) -> Self {
+        Source {
+            species,
+            ..self
+        }
+    }
+
+    /*
+     * How much fluid the source moves this step, given the current fluid and
+     * pressure in its cell. Positive amounts are added, negative removed
+     */
+    pub(crate) fn amount(&self, tick: u64, dt: f32, fluid: f32, pressure: f32) -> f32 {
+        if self.paused {
+            return 0f32;
+        }
+        let amount = match self.kind {
+            SourceKind::Constant { rate } => rate * dt,
+            SourceKind::Pulsed { amount, interval } => {
+                if interval == 0 || tick.is_multiple_of(interval as u64) {
+                    amount
+                } else {
+                    0f32
+                }
+            },
+            SourceKind::Reservoir { rate, remaining } => (rate * dt).min(remaining),
+            SourceKind::PressureLimited { rate, limit } => {
+                if self.drain {
+                    (rate * dt).min(pressure - limit)
+                } else {
+                    (rate * dt).min(limit - pressure)
+                }
+            }
+        };
+        let amount = amount.max(0f32);
+        if self.drain {
+            -amount.min(fluid)
+        } else {
+            amount
+        }
+    }
+
+    pub(crate) fn consume(&mut self, amount: f32) {
+        if let SourceKind::Reservoir { ref mut remaining, .. } = self.kind {
+            *remaining = (*remaining - amount.abs()).max(0f32);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpeciesId(pub(crate) usize);
+
+impl SpeciesId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Species {
+    pub name: String,
+    pub viscocity: f32,
+    pub density: f32,
+}
+
+impl Species {
+    pub fn new(name: &str, viscocity: f32, density: f32) -> Self {
+        Species {
+            name: name.to_string(),
+            viscocity,
+            density,
+        }
+    }
+}
+
+impl Default for Species {
+    fn default() -> Self {
+        Species::new("fluid", 1f32, 1f32)
+    }
+}
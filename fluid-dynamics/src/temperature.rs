@@ -0,0 +1,59 @@
+use mint::{Point2};
+
+/*
+ * Settings of the optional temperature field. Rates are per unit of time like
+ * the rest of the grid. Buoyancy scales how much a difference in temperature
+ * between two connected cells favours flow towards the colder one, so fluid
+ * is pushed away from heat and pools where it is cold
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Temperature {
+    pub ambient: f32,
+    pub conductivity: f32,
+    pub cooling: f32,
+    pub buoyancy: f32,
+}
+
+impl Temperature {
+    pub fn new(ambient: f32) -> Self {
+        Temperature {
+            ambient,
+            ..Temperature::default()
+        }
+    }
+}
+
+impl Default for Temperature {
+    fn default() -> Self {
+        Temperature {
+            ambient: 20f32,
+            conductivity: 0.5,
+            cooling: 0.05,
+            buoyancy: 0.02,
+        }
+    }
+}
+
+/*
+ * Pulls the temperature of its cell towards its own, at rate per unit of time
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeatSource {
+    pub position: Point2<usize>,
+    pub temperature: f32,
+    pub rate: f32,
+    pub paused: bool,
+}
+
+impl HeatSource {
+    pub fn new<T: Into<Point2<usize>>>(position: T, temperature: f32, rate: f32) -> Self {
+        HeatSource {
+            position: position.into(),
+            temperature,
+            rate,
+            paused: false,
+        }
+    }
+}
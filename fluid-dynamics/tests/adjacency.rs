@@ -0,0 +1,95 @@
+mod common;
+
+#[cfg(test)]
+mod adjacency {
+    use fluid_dynamics::{FluidGrid, Neighbourhood, SolidGrid};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn invalidated_cells_reconnect() {
+        let mut g: SolidGrid = "
+            00100
+            00100
+            00100
+        ".parse().unwrap();
+        let mut grid = FluidGrid::new(5, 3);
+
+        grid.set_fluid([0, 1], 30f32).unwrap();
+        grid.step(3, &g);
+        assert_fluid_eq(*grid.get_fluid([3, 1]).unwrap(), 0f32);
+
+        g.set_solid([2, 1], false);
+        grid.flow(&g);
+        assert_fluid_eq(*grid.get_fluid([2, 1]).unwrap(), 0f32);
+
+        grid.invalidate_cell([2, 1]);
+        grid.step(2, &g);
+        assert!(*grid.get_fluid([3, 1]).unwrap() > 0f32);
+        assert_fluid_eq(grid.total_fluid_level(), 30f32);
+    }
+
+    #[test]
+    fn invalidating_a_cell_matches_a_full_rebuild() {
+        let mut g = SolidGrid::new(6, 6).with_neighbourhood(Neighbourhood::Moore);
+        g.set_solid([3, 2], true);
+        let mut patched = FluidGrid::new(6, 6);
+        let mut rebuilt = FluidGrid::new(6, 6);
+        for grid in [&mut patched, &mut rebuilt] {
+            grid.set_fluid([1, 1], 20f32).unwrap();
+            grid.set_fluid([4, 4], 10f32).unwrap();
+            grid.step(2, &g);
+        }
+
+        g.set_solid([3, 2], false);
+        g.set_solid([2, 3], true);
+        patched.invalidate_cell([3, 2]);
+        patched.invalidate_cell([2, 3]);
+        rebuilt.invalidate_all();
+        patched.step(4, &g);
+        rebuilt.step(4, &g);
+
+        for (a, b) in patched.iter().zip(rebuilt.iter()) {
+            assert_eq!(a.1.to_bits(), b.1.to_bits());
+        }
+    }
+
+    #[test]
+    fn closing_cells_push_their_fluid_out() {
+        let mut g = SolidGrid::new(3, 3);
+        let mut grid = FluidGrid::new(3, 3);
+        grid.set_fluid([1, 1], 9f32).unwrap();
+        grid.flow(&g);
+
+        g.set_solid([1, 1], true);
+        grid.invalidate_cell([1, 1]);
+        assert!(grid.flow(&g).is_conserved());
+        assert_eq!(grid.get_fluid([1, 1]), Some(&0f32));
+        assert_fluid_eq(grid.total_fluid_level(), 9f32);
+
+        grid.step(20, &g);
+        assert_eq!(grid.get_fluid([1, 1]), Some(&0f32));
+        assert_fluid_eq(grid.total_fluid_level(), 9f32);
+    }
+
+    #[test]
+    fn sealed_cells_lose_their_fluid() {
+        let mut g: SolidGrid = "
+            010
+            111
+            010
+        ".parse().unwrap();
+        let mut grid = FluidGrid::new(3, 3);
+        grid.set_fluid([1, 1], 4f32).unwrap();
+        grid.set_fluid([0, 0], 1f32).unwrap();
+        g.set_solid([1, 1], false);
+        grid.flow(&g);
+
+        g.set_solid([1, 1], true);
+        grid.invalidate_cell([1, 1]);
+        let report = grid.flow(&g);
+        assert!(report.is_conserved());
+        assert_fluid_eq(report.drained, 4f32);
+        assert_fluid_eq(grid.total_fluid_level(), 1f32);
+    }
+}
@@ -1,100 +1,10 @@
+mod common;
+
 #[cfg(test)]
 mod basic_flow {
-    use mint::{Point2};
-
-    use fluid_dynamics::{FluidGrid};
-    use fluid_dynamics::{ConnectionGrid};
-
-    struct Grid {
-        grid: Vec<u8>,
-        w: usize,
-        h: usize
-    }
-
-    impl Grid {
-        fn new(w: usize, h: usize) -> Self {
-            Grid {
-                grid: vec![0u8; w * h],
-                w,
-                h
-            }
-        }
-
-        fn from_str(w: usize, h: usize, data: &str) -> Self {
-            let mut grid = vec![];
-            data.chars().for_each(|c| {
-                match c {
-                    '0' => grid.push(0),
-                    '1' => grid.push(1),
-                    _ => {}
-                }
-            });
-            Grid {
-                grid: grid,
-                w,
-                h
-            }
-        }
+    use fluid_dynamics::{FluidError, FluidGrid, SolidGrid, Species};
 
-        fn index<T: Into<Point2<usize>>>(&self, point: T) -> usize {
-            let point = point.into();
-            if point.x >= self.w || point.y >= self.h {
-                (self.w * self.h) + 1
-            } else {
-                point.x + (point.y * self.w)
-            }
-        }
-    }
-
-    impl ConnectionGrid for Grid {
-        fn get_connections<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Point2<usize>> {
-            let point = pos.into();
-            let mut connections = vec![];
-            if point.x != 0 {
-                let nx = (point.x - 1) as usize;
-                let ny = point.y as usize;
-                if self.grid.get(self.index([nx, ny])) == Some(&0u8) {
-                    connections.push([point.x - 1, point.y].into());
-                }
-            }
-            if point.y != 0 {
-                let nx = point.x as usize;
-                let ny = (point.y - 1) as usize;
-                if self.grid.get(self.index([nx, ny])) == Some(&0u8) {
-                    connections.push([point.x, point.y - 1].into());
-                }
-            }
-            if point.x < 4 {
-                let nx = (point.x + 1) as usize;
-                let ny = point.y as usize;
-                if self.grid.get(self.index([nx, ny])) == Some(&0u8) {
-                    connections.push([point.x + 1, point.y].into());
-                }
-            }
-            if point.y < 4 {
-                let nx = point.x as usize;
-                let ny = (point.y + 1) as usize;
-                if self.grid.get(self.index([nx, ny])) == Some(&0u8) {
-                    connections.push([point.x, point.y + 1].into());
-                }
-            }
-            connections
-        }
-
-        fn is_solid<T: Into<Point2<usize>>>(&self, pos: T) -> bool {
-            let pos = pos.into();
-            if pos.x >= self.w || pos.y >= self.h {
-                return true;
-            }
-            let idx = self.index(pos);
-            self.grid.get(idx) == Some(&1u8) || self.grid.get(idx) == None
-        }
-    }
-
-    fn assert_fluid_eq(fluid: f32, target: f32) {
-        println!("Comparing fluid level: {:.5} - {:5}", fluid, target);
-        assert!((fluid - target).abs() < 0.001);
-    }
+    use crate::common::{assert_fluid_eq};
 
     #[test]
     fn basic_setting_and_getting() {
@@ -103,27 +13,81 @@ mod basic_flow {
         assert_eq!(grid.get_fluid([0, 0]), Some(&0f32));
         assert_fluid_eq(grid.total_fluid_level(), 0f32);
 
-        assert_eq!(grid.set_fluid([2, 2], 3f32), Some(3f32));
+        assert_eq!(grid.set_fluid([2, 2], 3f32), Ok(3f32));
         assert_eq!(grid.get_fluid([2, 2]), Some(&3f32));
         assert_fluid_eq(grid.total_fluid_level(), 3f32);
 
-        assert_eq!(grid.set_fluid([20, 2], 3f32), None);
+        assert_eq!(grid.set_fluid([20, 2], 3f32), Err(FluidError::OutOfBounds([20, 2].into())));
 
-        assert_eq!(grid.set_fluid([3, 3], 10f32), Some(10f32));
+        assert_eq!(grid.set_fluid([3, 3], 10f32), Ok(10f32));
         assert_eq!(grid.get_fluid([3, 3]), Some(&10f32));
 
-        assert_eq!(grid.add_fluid([3, 3], 10f32), Some(20f32));
+        assert_eq!(grid.add_fluid([3, 3], 10f32), Ok(20f32));
         assert_eq!(grid.get_fluid([3, 3]), Some(&20f32));
     }
 
+    #[test]
+    fn setting_writes_a_single_cell() {
+        let mut grid = FluidGrid::new(3, 3);
+
+        grid.set_fluid([1, 0], 1f32).unwrap();
+        grid.set_fluid([0, 0], 2f32).unwrap();
+        assert_eq!(grid.get_fluid([1, 0]), Some(&1f32));
+        assert_eq!(grid.iter().count(), 9);
+        assert_eq!(grid.add_fluid([3, 0], 1f32), Err(FluidError::OutOfBounds([3, 0].into())));
+        assert_eq!(grid.get_fluid([0, 1]), Some(&0f32));
+    }
+
+    #[test]
+    fn invalid_amounts_are_rejected() {
+        let mut grid = FluidGrid::new(3, 3);
+        grid.set_fluid([1, 1], 2f32).unwrap();
+
+        assert_eq!(grid.set_fluid([1, 1], -1f32), Err(FluidError::NegativeAmount(-1f32)));
+        assert_eq!(grid.add_fluid([1, 1], -3f32), Err(FluidError::NegativeAmount(-1f32)));
+        assert_eq!(grid.add_fluid([1, 1], -2f32), Ok(0f32));
+        assert!(matches!(grid.set_fluid([1, 1], f32::NAN), Err(FluidError::NonFinite(_))));
+        assert_eq!(grid.add_fluid([1, 1], f32::INFINITY), Err(FluidError::NonFinite(f32::INFINITY)));
+        assert!(grid.set_viscocity(f32::NAN).is_err());
+        assert!(grid.add_species(Species::new("gas", 1f32, f32::NEG_INFINITY)).is_err());
+        assert_eq!(grid.set_capacity(f32::INFINITY), Ok(()));
+        assert_fluid_eq(grid.total_fluid_level(), 0f32);
+    }
+
+    #[test]
+    fn solid_cells_are_rejected() {
+        let mut grid = FluidGrid::new(3, 3);
+        let g: SolidGrid = "
+            000
+            010
+            000
+        ".parse().unwrap();
+
+        assert_eq!(grid.set_fluid([1, 1], 1f32), Ok(1f32));
+        grid.set_fluid([1, 1], 0f32).unwrap();
+        grid.flow(&g);
+        assert_eq!(grid.set_fluid([1, 1], 1f32), Err(FluidError::CellSolid([1, 1].into())));
+        grid.invalidate_cell([1, 1]);
+        assert_eq!(grid.set_fluid([1, 1], 1f32), Ok(1f32));
+        grid.flow(&g);
+        assert_eq!(grid.get_fluid([1, 1]), Some(&0f32));
+        assert_fluid_eq(grid.total_fluid_level(), 1f32);
+
+        let mut grid = FluidGrid::new(3, 3);
+        grid.update_connections(&g);
+        assert_eq!(grid.set_fluid([1, 1], 1f32), Err(FluidError::CellSolid([1, 1].into())));
+        assert_eq!(grid.add_fluid([1, 1], 1f32), Err(FluidError::CellSolid([1, 1].into())));
+        assert_eq!(grid.total_fluid_level(), 0f32);
+    }
+
     #[test]
     fn flow_from_corner() {
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([0, 0], 10f32), Some(10f32));
+        assert_eq!(grid.set_fluid([0, 0], 10f32), Ok(10f32));
         assert_fluid_eq(grid.total_fluid_level(), 10f32);
 
-        let g = Grid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
 
         grid.flow(&g);
 
@@ -137,10 +101,10 @@ mod basic_flow {
     fn flow_in_center() {
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([2, 2], 15f32), Some(15f32));
+        assert_eq!(grid.set_fluid([2, 2], 15f32), Ok(15f32));
         assert_fluid_eq(grid.total_fluid_level(), 15f32);
 
-        let g = Grid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
 
         grid.flow(&g);
 
@@ -156,11 +120,11 @@ mod basic_flow {
     fn flow_between_two() {
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([0, 0], 10f32), Some(10f32));
-        assert_eq!(grid.set_fluid([1, 0], 20f32), Some(20f32));
+        assert_eq!(grid.set_fluid([0, 0], 10f32), Ok(10f32));
+        assert_eq!(grid.set_fluid([1, 0], 20f32), Ok(20f32));
         assert_fluid_eq(grid.total_fluid_level(), 30f32);
 
-        let g = Grid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
 
         grid.flow(&g);
 
@@ -176,10 +140,10 @@ mod basic_flow {
     fn constant_fluid_level() {
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([1, 0], 21f32), Some(21f32));
+        assert_eq!(grid.set_fluid([1, 0], 21f32), Ok(21f32));
         assert_fluid_eq(grid.total_fluid_level(), 21f32);
 
-        let g = Grid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
 
         grid.flow(&g);
         assert_fluid_eq(grid.total_fluid_level(), 21f32);
@@ -198,9 +162,9 @@ mod basic_flow {
     fn level_equalizer() {
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([0, 0], 50f32), Some(50f32));
+        assert_eq!(grid.set_fluid([0, 0], 50f32), Ok(50f32));
 
-        let g = Grid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
 
         for _ in 0..500 {
             grid.flow(&g);
@@ -212,15 +176,15 @@ mod basic_flow {
     #[test]
     fn obstacle_flow() {
         let mut grid = FluidGrid::new(5, 5);
-        let g = Grid::from_str(5, 5, "
+        let g: SolidGrid = "
             00000
             00000
             11011
             00010
             00000
-        ");
+        ".parse().unwrap();
 
-        assert_eq!(grid.set_fluid([0, 0], 50f32), Some(50f32));
+        assert_eq!(grid.set_fluid([0, 0], 50f32), Ok(50f32));
 
         for _ in 0..500 {
             grid.flow(&g);
@@ -239,23 +203,23 @@ mod basic_flow {
 
     #[test]
     fn basic_pressure() {
-        let g = Grid::from_str(5, 5, "
+        let g: SolidGrid = "
             00000
             00000
             11011
             00010
             00000
-        ");
+        ".parse().unwrap();
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([0, 0], 50f32), Some(50f32));
+        assert_eq!(grid.set_fluid([0, 0], 50f32), Ok(50f32));
 
         for _ in 0..500 {
             grid.flow(&g);
         }
         assert!(grid.is_stable());
         assert_fluid_eq(grid.total_fluid_level(), 50f32);
-        assert_fluid_eq(*grid.get_pressure([0, 0]).unwrap(), 0f32);
+        assert_fluid_eq(*grid.get_pressure([0, 0]).unwrap(), *grid.get_fluid([0, 0]).unwrap());
 
         assert!(*grid.get_pressure([0, 2]).unwrap() > 5f32);
         assert!(*grid.get_pressure([0, 2]).unwrap() < 6f32);
@@ -266,16 +230,16 @@ mod basic_flow {
 
     #[test]
     fn basic_velocity_flow() {
-        let g = Grid::from_str(5, 5, "
+        let g: SolidGrid = "
             00000
             11011
             01010
             00000
             00000
-        ");
+        ".parse().unwrap();
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([2, 0], 60f32), Some(60f32));
+        assert_eq!(grid.set_fluid([2, 0], 60f32), Ok(60f32));
         grid.flow(&g);
 
         assert_fluid_eq(*grid.get_fluid([2, 0]).unwrap(), 24f32);
@@ -314,16 +278,16 @@ mod basic_flow {
     fn constant_fluid_after_adding() {
         let mut grid = FluidGrid::new(5, 5);
 
-        assert_eq!(grid.set_fluid([2, 2], 100f32), Some(100f32));
+        assert_eq!(grid.set_fluid([2, 2], 100f32), Ok(100f32));
         assert_fluid_eq(grid.total_fluid_level(), 100f32);
 
-        let g = Grid::from_str(5, 5, "
+        let g: SolidGrid = "
             11111
             10001
             10001
             10001
             11111
-        ");
+        ".parse().unwrap();
 
         for _ in 0..100 {
             grid.flow(&g);
@@ -331,7 +295,7 @@ mod basic_flow {
         assert_fluid_eq(grid.total_fluid_level(), 100f32);
 
         assert_fluid_eq(*grid.get_fluid([2, 2]).unwrap(), 11.1111f32);
-        grid.add_fluid([2, 2], 100f32);
+        grid.add_fluid([2, 2], 100f32).unwrap();
         assert_fluid_eq(*grid.get_fluid([2, 2]).unwrap(), 111.1111f32);
         assert_fluid_eq(grid.total_fluid_level(), 200f32);
 
@@ -345,12 +309,12 @@ mod basic_flow {
     #[test]
     fn viscocity_reduce_flow() {
         let mut grid = FluidGrid::new(5, 5);
-        grid.set_viscocity(0.5);
+        grid.set_viscocity(0.5).unwrap();
 
-        assert_eq!(grid.set_fluid([0, 0], 10f32), Some(10f32));
+        assert_eq!(grid.set_fluid([0, 0], 10f32), Ok(10f32));
         assert_fluid_eq(grid.total_fluid_level(), 10f32);
 
-        let g = Grid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
 
         grid.flow(&g);
 
@@ -363,12 +327,12 @@ mod basic_flow {
     #[test]
     fn viscocity_increase_flow() {
         let mut grid = FluidGrid::new(5, 5);
-        grid.set_viscocity(1.5);
+        grid.set_viscocity(1.5).unwrap();
 
-        assert_eq!(grid.set_fluid([0, 0], 10f32), Some(10f32));
+        assert_eq!(grid.set_fluid([0, 0], 10f32), Ok(10f32));
         assert_fluid_eq(grid.total_fluid_level(), 10f32);
 
-        let g = Grid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
 
         grid.flow(&g);
 
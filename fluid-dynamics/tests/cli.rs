@@ -0,0 +1,69 @@
+mod common;
+
+#[cfg(test)]
+mod cli {
+    use std::fs;
+    use std::path::{PathBuf};
+    use std::process::{Command, Output};
+
+    use crate::common::{assert_fluid_eq};
+
+    fn map(name: &str, rows: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("fluid-sim-{}-{}.map", name, std::process::id()));
+        fs::write(&path, rows).unwrap();
+        path
+    }
+
+    fn sim(map: &PathBuf, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_fluid-sim"))
+            .arg(map)
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn csv_statistics_per_step() {
+        let map = map("csv", "#####\n#...#\n#####\n");
+        let output = sim(&map, &["--steps", "3", "--format", "csv", "--fluid", "1,1,3", "--emitter", "3,1,1"]);
+        assert!(output.status.success());
+
+        let text = String::from_utf8(output.stdout).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("step,total,"));
+        let last = lines[3].split(',').collect::<Vec<_>>();
+        assert_eq!(last[0], "3");
+        assert_fluid_eq(last[1].parse().unwrap(), 6f32);
+    }
+
+    #[test]
+    fn grid_and_ppm_frames() {
+        let map = map("frames", "....\n.##.\n....\n");
+        let output = sim(&map, &["--steps", "4", "--every", "2", "--fluid", "0,0,8"]);
+        let text = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(text.matches("step").count(), 3);
+        assert!(text.starts_with("step 0\n 08.000 "));
+
+        let out = std::env::temp_dir().join(format!("fluid-sim-frames-{}", std::process::id()));
+        let output = sim(&map, &["--steps", "2", "--format", "ppm", "--scale", "2", "--out", out.to_str().unwrap(), "--fluid", "0,0,8"]);
+        assert!(output.status.success());
+        let frame = fs::read(out.join("frame_00002.ppm")).unwrap();
+        assert!(frame.starts_with(b"P6\n8 6\n255\n"));
+        assert_eq!(frame.len(), "P6\n8 6\n255\n".len() + 8 * 6 * 3);
+        fs::remove_dir_all(out).unwrap();
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        let map = map("errors", "###\n#.#\n###\n");
+        let output = sim(&map, &["--fluid", "0,0,1"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("0,0 is a wall"));
+
+        let output = sim(&map, &["--steps"]);
+        assert_eq!(output.status.code(), Some(2));
+        let output = sim(&map, &["--format", "bmp"]);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("unknown format 'bmp'"));
+    }
+}
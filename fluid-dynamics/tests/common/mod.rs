@@ -0,0 +1,41 @@
+/*
+ * Helpers shared by the integration tests, pulled in with `mod common;`. Not
+ * every test file uses every helper
+ */
+#![allow(dead_code)]
+
+use fluid_dynamics::{ConnectionGrid, FluidGrid, SolidGrid};
+
+pub fn assert_fluid_eq(fluid: f32, target: f32) {
+    println!("Comparing fluid level: {:.5} - {:5}", fluid, target);
+    assert!((fluid - target).abs() < 0.001);
+}
+
+/*
+ * Fluid poured into 1,1 of an open grid and left to flow for a few steps,
+ * pour can add more before the first step
+ */
+pub fn flowing_grid(width: usize, height: usize, steps: u32, pour: impl FnOnce(&mut FluidGrid)) -> FluidGrid {
+    let mut grid = FluidGrid::new(width, height);
+    grid.set_fluid([1, 1], 10f32).unwrap();
+    pour(&mut grid);
+    grid.step(steps, &SolidGrid::new(width, height));
+    grid
+}
+
+/*
+ * Rooms split by walls every 16 columns with a gap every 4 rows, and fluid
+ * scattered over the open cells
+ */
+pub fn setup(width: usize, height: usize) -> (FluidGrid, SolidGrid) {
+    let walls = SolidGrid::from_fn(width, height, |pos| pos.x % 16 == 0 && pos.y % 4 != 0);
+    let mut grid = FluidGrid::new(width, height);
+    for y in (1..height).step_by(7) {
+        for x in (1..width).step_by(5) {
+            if !walls.is_solid([x, y]) {
+                grid.set_fluid([x, y], 100f32).unwrap();
+            }
+        }
+    }
+    (grid, walls)
+}
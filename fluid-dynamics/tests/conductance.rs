@@ -0,0 +1,41 @@
+mod common;
+
+#[cfg(test)]
+mod conductance {
+    use fluid_dynamics::{FluidGrid, SolidGrid};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn grates_throttle_flow() {
+        let mut open = FluidGrid::new(2, 1);
+        let mut grate = FluidGrid::new(2, 1);
+        let mut g = SolidGrid::new(2, 1);
+        open.set_fluid([0, 0], 10f32).unwrap();
+        grate.set_fluid([0, 0], 10f32).unwrap();
+
+        open.flow(&g);
+        g.set_conductance([0, 0], [1, 0], 0.5);
+        grate.flow(&g);
+        assert_fluid_eq(*open.get_fluid([1, 0]).unwrap(), 2f32);
+        assert_fluid_eq(*grate.get_fluid([1, 0]).unwrap(), 1f32);
+
+        g.set_conductance([0, 0], [1, 0], 0f32);
+        grate.invalidate_cell([0, 0]);
+        grate.step(10, &g);
+        assert_fluid_eq(*grate.get_fluid([1, 0]).unwrap(), 1f32);
+    }
+
+    #[test]
+    fn valves_only_open_one_way() {
+        let mut grid = FluidGrid::new(3, 1);
+        let mut g = SolidGrid::new(3, 1);
+        g.set_one_way([1, 0], [2, 0]);
+        grid.set_fluid([0, 0], 10f32).unwrap();
+        grid.set_fluid([2, 0], 5f32).unwrap();
+
+        grid.step(300, &g);
+        assert_fluid_eq(grid.total_fluid_level(), 15f32);
+        assert_fluid_eq(*grid.get_fluid([2, 0]).unwrap(), 15f32);
+    }
+}
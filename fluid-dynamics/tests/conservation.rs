@@ -0,0 +1,65 @@
+mod common;
+
+#[cfg(test)]
+mod conservation {
+    use fluid_dynamics::{ConnectionGrid, FluidGrid, SolidGrid};
+    use mint::{Point2};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn flow_conserves_fluid() {
+        let g: SolidGrid = "
+            00000
+            11011
+            01010
+            00000
+            00100
+        ".parse().unwrap();
+        let mut grid = FluidGrid::new(5, 5);
+        grid.set_viscocity(1.5).unwrap();
+
+        assert_eq!(grid.set_fluid([2, 0], 60f32), Ok(60f32));
+        assert_eq!(grid.set_fluid([0, 4], 7f32), Ok(7f32));
+
+        for _ in 0..200 {
+            let report = grid.flow(&g);
+            assert!(report.is_conserved());
+            assert_fluid_eq(report.mass_in, report.mass_out);
+            assert_fluid_eq(report.before, 67f32);
+            assert_fluid_eq(report.after, 67f32);
+            assert!(grid.iter().all(|(_, f)| f >= 0f32));
+        }
+        assert_fluid_eq(grid.total_fluid_level(), 67f32);
+    }
+
+    #[test]
+    fn fluid_stays_out_of_listed_walls() {
+        struct Leaky(SolidGrid);
+
+        impl ConnectionGrid for Leaky {
+            fn get_connections<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Point2<usize>> {
+                let size = self.0.dimensions();
+                self.0.neighbourhood().neighbours(pos, size.x, size.y).into_iter().map(|(pos, _)| pos).collect()
+            }
+
+            fn is_solid<T: Into<Point2<usize>>>(&self, pos: T) -> bool {
+                self.0.is_solid(pos)
+            }
+        }
+
+        let g = Leaky("
+            000
+            010
+            000
+        ".parse().unwrap());
+        let mut grid = FluidGrid::new(3, 3);
+        grid.set_fluid([1, 0], 10f32).unwrap();
+
+        for _ in 0..20 {
+            assert!(grid.flow(&g).is_conserved());
+            assert_eq!(grid.get_fluid([1, 1]), Some(&0f32));
+        }
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(all(test, feature = "png", feature = "gif"))]
+mod encode {
+    use fluid_dynamics::{write_gif, Field, FluidGrid, HeatMap};
+
+    #[test]
+    fn png_frames() {
+        let mut grid = FluidGrid::new(4, 3);
+        grid.set_fluid([1, 1], 5f32).unwrap();
+        let mut bytes = vec![];
+        HeatMap::new(Field::Fluid).with_scale(3).render(&grid).write_png(&mut bytes).unwrap();
+
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(&bytes[16..24], &[0, 0, 0, 12, 0, 0, 0, 9]);
+    }
+
+    #[test]
+    fn gif_animations() {
+        let mut grid = FluidGrid::new(4, 3);
+        let heat_map = HeatMap::new(Field::Fluid).with_range(0f32, 5f32);
+        let mut frames = vec![heat_map.render(&grid)];
+        grid.set_fluid([1, 1], 5f32).unwrap();
+        frames.push(heat_map.render(&grid));
+        let mut bytes = vec![];
+        write_gif(&frames, 10, &mut bytes).unwrap();
+
+        assert!(bytes.starts_with(b"GIF89a"));
+        assert_eq!(&bytes[6..10], &[4, 0, 3, 0]);
+
+        frames.push(heat_map.with_scale(2).render(&grid));
+        assert!(write_gif(&frames, 10, &mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+#[cfg(test)]
+mod iter {
+    use fluid_dynamics::{FluidError, FluidGrid, Rect, SolidGrid, Species};
+
+    use crate::common::{self, assert_fluid_eq};
+
+    fn flowing_grid() -> FluidGrid {
+        common::flowing_grid(5, 4, 2, |grid| {
+            let gas = grid.add_species(Species::new("gas", 0.5, 0.5)).unwrap();
+            grid.set_species_fluid(gas, [3, 2], 4f32).unwrap();
+        })
+    }
+
+    #[test]
+    fn cells_match_getters() {
+        let grid = flowing_grid();
+
+        assert_eq!(grid.iter_cells().len(), 20);
+        for cell in grid.iter_cells() {
+            assert_eq!(Some(cell.fluid), grid.get_total_fluid(cell.position));
+            assert_eq!(Some(&cell.pressure), grid.get_pressure(cell.position));
+            assert_eq!(Some(&cell.velocity), grid.get_velocity(cell.position));
+        }
+        assert_eq!(grid.get_cell([4, 3]), grid.iter_cells().next_back());
+        assert_eq!(grid.get_cell([5, 0]), None);
+    }
+
+    #[test]
+    fn rect_is_clipped_and_reversible() {
+        let grid = flowing_grid();
+        let cells = grid.iter_rect(Rect::new(3, 2, 4, 4));
+
+        assert_eq!(cells.len(), 4);
+        let positions = cells.rev().map(|cell| cell.position).collect::<Vec<_>>();
+        assert_eq!(positions, vec![[4, 3].into(), [3, 3].into(), [4, 2].into(), [3, 2].into()]);
+
+        let mut fluid = grid.iter();
+        assert_eq!(fluid.len(), 20);
+        assert_eq!(fluid.next_back().map(|(pos, _)| pos), Some([4, 3].into()));
+        assert_eq!(fluid.len(), 19);
+        assert_eq!(fluid.next().map(|(pos, _)| pos), Some([0, 0].into()));
+    }
+
+    #[test]
+    fn rect_at_the_end_of_the_range_is_empty() {
+        let grid = flowing_grid();
+        assert_eq!(grid.iter_rect(Rect::new(usize::MAX, 0, 5, 5)).len(), 0);
+        assert_eq!(grid.iter_rect(Rect::new(0, usize::MAX - 2, 5, 5)).len(), 0);
+        assert_eq!(grid.iter_rect(Rect::new(2, 1, usize::MAX, usize::MAX)).len(), 9);
+        assert_eq!(Rect::new(usize::MAX - 1, 0, 5, 5).intersection(&Rect::new(0, 0, usize::MAX, 3)),
+            Rect::new(usize::MAX - 1, 0, 1, 3));
+    }
+
+    #[test]
+    fn iter_mut_changes_cells() {
+        let mut grid = FluidGrid::new(4, 4);
+        let g = SolidGrid::new(4, 4);
+        grid.step(1, &g);
+        assert!(grid.is_stable());
+
+        for mut cell in grid.iter_mut() {
+            if cell.position().y == 0 {
+                cell.set_fluid(2f32).unwrap();
+            }
+            assert_eq!(cell.add_fluid(-5f32), Err(FluidError::NegativeAmount(cell.fluid() - 5f32)));
+            assert!(cell.set_velocity([f32::NAN, 0f32].into()).is_err());
+        }
+        assert_fluid_eq(grid.total_fluid_level(), 8f32);
+        assert_eq!(grid.iter_mut().next_back().map(|cell| cell.position()), Some([3, 3].into()));
+
+        grid.step(5, &g);
+        assert_fluid_eq(grid.total_fluid_level(), 8f32);
+        assert!(*grid.get_fluid([0, 3]).unwrap() > 0f32);
+    }
+
+    #[test]
+    fn iter_mut_refuses_solid_cells() {
+        let mut grid = FluidGrid::new(3, 1);
+        let g: SolidGrid = "010".parse().unwrap();
+        grid.flow(&g);
+
+        let results = grid.iter_mut().map(|mut cell| cell.set_fluid(1f32)).collect::<Vec<_>>();
+        assert_eq!(results, vec![Ok(1f32), Err(FluidError::CellSolid([1, 0].into())), Ok(1f32)]);
+    }
+}
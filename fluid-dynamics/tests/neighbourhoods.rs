@@ -0,0 +1,115 @@
+mod common;
+
+#[cfg(test)]
+mod neighbourhoods {
+    use fluid_dynamics::{ConnectionGrid, FluidError, FluidGrid, Neighbourhood, Offset, SolidGrid};
+    use mint::{Point2};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn moore_flow_weights_diagonals() {
+        let mut grid = FluidGrid::new(5, 5);
+
+        assert_eq!(grid.set_fluid([2, 2], 15f32), Ok(15f32));
+
+        let g = SolidGrid::new(5, 5).with_neighbourhood(Neighbourhood::Moore);
+        let divisor = 5f32 + 4f32 * std::f32::consts::FRAC_1_SQRT_2;
+
+        grid.flow(&g);
+
+        assert_fluid_eq(*grid.get_fluid([2, 2]).unwrap(), 15f32 / divisor);
+        assert_fluid_eq(*grid.get_fluid([1, 2]).unwrap(), 15f32 / divisor);
+        assert_fluid_eq(*grid.get_fluid([1, 1]).unwrap(), 15f32 * std::f32::consts::FRAC_1_SQRT_2 / divisor);
+        assert_fluid_eq(*grid.get_fluid([3, 3]).unwrap(), 15f32 * std::f32::consts::FRAC_1_SQRT_2 / divisor);
+        assert_fluid_eq(grid.total_fluid_level(), 15f32);
+    }
+
+    #[test]
+    fn hex_flow_reaches_six_neighbours() {
+        let mut grid = FluidGrid::new(5, 5);
+
+        assert_eq!(grid.set_fluid([2, 2], 14f32), Ok(14f32));
+
+        let g = SolidGrid::new(5, 5).with_neighbourhood(Neighbourhood::HexOffset);
+
+        grid.flow(&g);
+
+        assert_fluid_eq(*grid.get_fluid([2, 2]).unwrap(), 2f32);
+        for pos in [[1, 2], [3, 2], [1, 1], [2, 1], [1, 3], [2, 3]].iter() {
+            assert_fluid_eq(*grid.get_fluid(*pos).unwrap(), 2f32);
+        }
+        assert_fluid_eq(*grid.get_fluid([3, 1]).unwrap(), 0f32);
+        assert_fluid_eq(grid.total_fluid_level(), 14f32);
+    }
+
+    #[test]
+    fn stencil_weights_bias_flow() {
+        let mut grid = FluidGrid::new(5, 5);
+
+        assert_eq!(grid.set_fluid([2, 2], 10f32), Ok(10f32));
+
+        let g = SolidGrid::new(5, 5).with_neighbourhood(Neighbourhood::Stencil(vec![
+            Offset::new(0, 1, 3f32),
+            Offset::new(0, -1, 1f32),
+        ]));
+
+        grid.flow(&g);
+
+        assert_fluid_eq(*grid.get_fluid([2, 3]).unwrap(), 6f32);
+        assert_fluid_eq(*grid.get_fluid([2, 1]).unwrap(), 2f32);
+        assert_fluid_eq(*grid.get_fluid([2, 2]).unwrap(), 2f32);
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+    }
+
+    #[test]
+    fn bad_stencils_are_rejected() {
+        let bad = [Offset::new(0, 0, 1f32), Offset::new(1, 0, -1f32), Offset::new(1, 0, 0f32), Offset::new(0, 1, f32::NAN)];
+        for offset in bad.iter() {
+            let stencil = Neighbourhood::stencil(vec![Offset::new(-1, 0, 1f32), *offset]);
+            assert!(matches!(stencil, Err(FluidError::InvalidOffset(o)) if (o.dx, o.dy) == (offset.dx, offset.dy)));
+        }
+        assert!(Neighbourhood::stencil(vec![Offset::new(-1, 0, 1f32), Offset::new(1, 0, 2f32)]).is_ok());
+
+        let mut grid = FluidGrid::new(3, 1);
+        grid.set_fluid([1, 0], 5f32).unwrap();
+        grid.set_fluid([2, 0], 10f32).unwrap();
+        let g = SolidGrid::new(3, 1).with_neighbourhood(Neighbourhood::Stencil(vec![
+            Offset::new(-1, 0, -1f32),
+            Offset::new(0, 0, 1f32),
+            Offset::new(1, 0, 1f32),
+        ]));
+        grid.step(3, &g);
+
+        assert_eq!(grid.get_fluid([0, 0]), Some(&0f32));
+        assert!(grid.iter().all(|(_, fluid)| fluid.is_finite() && fluid >= 0f32));
+        assert!(grid.iter_cells().all(|cell| cell.velocity.x.is_finite() && cell.velocity.y.is_finite()));
+        assert_fluid_eq(grid.total_fluid_level(), 15f32);
+    }
+
+    #[test]
+    fn connections_to_the_same_cell_are_ignored() {
+        struct Loops(SolidGrid);
+
+        impl ConnectionGrid for Loops {
+            fn get_connections<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Point2<usize>> {
+                let pos = pos.into();
+                let mut connections = self.0.get_connections(pos);
+                connections.push(pos);
+                connections
+            }
+
+            fn is_solid<T: Into<Point2<usize>>>(&self, pos: T) -> bool {
+                self.0.is_solid(pos)
+            }
+        }
+
+        let mut grid = FluidGrid::new(3, 1);
+        grid.set_fluid([0, 0], 6f32).unwrap();
+        grid.step(2, &Loops(SolidGrid::new(3, 1)));
+
+        assert!(grid.iter_cells().all(|cell| cell.velocity.x.is_finite() && cell.velocity.y.is_finite()));
+        assert!(*grid.get_fluid([2, 0]).unwrap() > 0f32);
+        assert_fluid_eq(grid.total_fluid_level(), 6f32);
+    }
+}
@@ -0,0 +1,61 @@
+mod common;
+
+#[cfg(all(test, feature = "rayon"))]
+mod parallel_flow {
+    use fluid_dynamics::{ConnectionGrid, FluidGrid, SolidGrid, Species};
+    use rayon::prelude::*;
+
+    use crate::common;
+
+    fn setup(parallel: bool) -> (FluidGrid, SolidGrid) {
+        let (mut grid, walls) = common::setup(40, 30);
+        grid.set_parallel(parallel);
+        let gas = grid.add_species(Species::new("gas", 0.8, 0.5)).unwrap();
+        grid.set_species_fluid(gas, [31, 25], 60f32).unwrap();
+        (grid, walls)
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let (mut serial, g) = setup(false);
+        let (mut parallel, _) = setup(true);
+        assert!(!serial.parallel());
+        assert!(parallel.parallel());
+
+        for _ in 0..25 {
+            serial.flow(&g);
+            parallel.flow(&g);
+        }
+
+        for y in 0..30 {
+            for x in 0..40 {
+                for (species, _) in serial.species() {
+                    assert_eq!(
+                        serial.get_species_fluid(species, [x, y]).unwrap().to_bits(),
+                        parallel.get_species_fluid(species, [x, y]).unwrap().to_bits()
+                    );
+                }
+                let (a, b) = (serial.get_velocity([x, y]).unwrap(), parallel.get_velocity([x, y]).unwrap());
+                assert_eq!((a.x.to_bits(), a.y.to_bits()), (b.x.to_bits(), b.y.to_bits()));
+            }
+        }
+        assert_eq!(serial.total_fluid_level().to_bits(), parallel.total_fluid_level().to_bits());
+    }
+
+    #[test]
+    fn parallel_iterators_match_serial() {
+        let (mut grid, g) = setup(true);
+        grid.step(5, &g);
+
+        let cells = grid.par_iter_cells().collect::<Vec<_>>();
+        assert_eq!(cells, grid.iter_cells().collect::<Vec<_>>());
+
+        let before = grid.iter().map(|(_, fluid)| fluid).collect::<Vec<_>>();
+        grid.par_iter_mut().filter(|cell| !g.is_solid(cell.position())).for_each(|mut cell| {
+            cell.set_fluid(cell.fluid() * 2f32).unwrap();
+        });
+        for ((_, fluid), before) in grid.iter().zip(before) {
+            assert_eq!(fluid, before * 2f32);
+        }
+    }
+}
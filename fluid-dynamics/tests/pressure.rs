@@ -0,0 +1,36 @@
+mod common;
+
+#[cfg(test)]
+mod pressure {
+    use fluid_dynamics::{FluidGrid, SolidGrid, Species};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn pressure_pushes_walls_away_from_fluid() {
+        let g: SolidGrid = "
+            00100
+            00100
+            00100
+            00000
+            00100
+        ".parse().unwrap();
+        let mut grid = FluidGrid::new(5, 5);
+        let water = grid.add_species(Species::new("water", 0f32, 2f32)).unwrap();
+        grid.set_viscocity(0f32).unwrap();
+
+        grid.set_fluid([1, 1], 4f32).unwrap();
+        grid.set_species_fluid(water, [1, 1], 1f32).unwrap();
+        grid.set_fluid([3, 1], 1f32).unwrap();
+        grid.flow(&g);
+
+        assert_fluid_eq(*grid.get_pressure([1, 1]).unwrap(), 6f32);
+        assert_fluid_eq(*grid.get_pressure([2, 1]).unwrap(), 7f32);
+        let force = grid.get_force([2, 1]).unwrap();
+        assert_fluid_eq(force.x, 5f32);
+        assert_fluid_eq(force.y, 0f32);
+        assert_fluid_eq(grid.get_pressure_differential([2, 1]).unwrap(), 5f32);
+        assert_fluid_eq(grid.get_pressure_differential([1, 1]).unwrap(), 0f32);
+        assert_fluid_eq(grid.get_pressure_differential([2, 0]).unwrap(), 0f32);
+    }
+}
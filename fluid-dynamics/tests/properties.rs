@@ -0,0 +1,292 @@
+#[cfg(test)]
+mod properties {
+    use std::fmt;
+
+    use mint::{Point2};
+    use proptest::prelude::*;
+    use proptest::collection::{vec};
+
+    use fluid_dynamics::{ConnectionGrid, FluidGrid, Neighbourhood};
+
+    const STEPS: u32 = 20;
+
+    /*
+     * A connection grid with a throttle on every directed connection, so
+     * connections can be closed, narrowed or one way. Leaky layouts list every
+     * neighbour as a connection, solid or not, and leave it to the solver to
+     * keep fluid out of walls. Failing layouts are printed as a map followed
+     * by every connection that is not fully open
+     */
+    #[derive(Clone)]
+    struct Layout {
+        width: usize,
+        height: usize,
+        solid: Vec<bool>,
+        throttle: Vec<f32>,
+        neighbourhood: Neighbourhood,
+        leaky: bool,
+    }
+
+    impl Layout {
+        fn index(&self, pos: Point2<usize>) -> Option<usize> {
+            if pos.x < self.width && pos.y < self.height {
+                Some(pos.x + pos.y * self.width)
+            } else {
+                None
+            }
+        }
+
+        fn slot(&self, from: Point2<usize>, to: Point2<usize>) -> Option<usize> {
+            let (dx, dy) = (to.x as isize - from.x as isize, to.y as isize - from.y as isize);
+            let k = self.neighbourhood
+                .offsets(from)
+                .iter()
+                .position(|offset| offset.dx == dx && offset.dy == dy)?;
+            Some(self.index(from)? * self.neighbourhood.size() + k)
+        }
+
+        fn points(&self) -> impl Iterator<Item = Point2<usize>> {
+            let width = self.width;
+            (0..self.width * self.height).map(move |i| [i % width, i / width].into())
+        }
+    }
+
+    impl ConnectionGrid for Layout {
+        fn get_connections<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Point2<usize>> {
+            let from = pos.into();
+            if self.is_solid(from) && !self.leaky {
+                return vec![];
+            }
+            self.neighbourhood
+                .neighbours(from, self.width, self.height)
+                .into_iter()
+                .map(|(to, _)| to)
+                .filter(|to| (self.leaky || !self.is_solid(*to)) && self.conductance(from, *to) > 0f32)
+                .collect()
+        }
+
+        fn is_solid<T: Into<Point2<usize>>>(&self, pos: T) -> bool {
+            self.index(pos.into()).is_none_or(|i| self.solid[i])
+        }
+
+        fn conductance<T: Into<Point2<usize>>>(&self, from: T, to: T) -> f32 {
+            self.slot(from.into(), to.into()).map_or(0f32, |i| self.throttle[i])
+        }
+
+        fn neighbourhood(&self) -> &Neighbourhood {
+            &self.neighbourhood
+        }
+    }
+
+    impl fmt::Debug for Layout {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "{:?}{}", self.neighbourhood, if self.leaky { " leaky" } else { "" })?;
+            for row in self.solid.chunks(self.width) {
+                let row = row.iter().map(|solid| if *solid { '#' } else { '.' }).collect::<String>();
+                writeln!(f, "{}", row)?;
+            }
+            for from in self.points() {
+                for (to, _) in self.neighbourhood.neighbours(from, self.width, self.height) {
+                    let conductance = self.conductance(from, to);
+                    if conductance < 1f32 {
+                        writeln!(f, "{},{} -> {},{}: {}", from.x, from.y, to.x, to.y, conductance)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Clone)]
+    struct Case {
+        layout: Layout,
+        fluid: Vec<f32>,
+        viscocity: f32,
+        capacity: Option<f32>,
+    }
+
+    impl fmt::Debug for Case {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.layout)?;
+            writeln!(f, "viscocity {} capacity {:?}", self.viscocity, self.capacity)?;
+            for row in self.fluid.chunks(self.layout.width) {
+                let row = row.iter().map(|fluid| format!("{:.3}", fluid)).collect::<Vec<_>>();
+                writeln!(f, "{}", row.join(" "))?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Case {
+        fn grid(&self) -> FluidGrid {
+            let layout = &self.layout;
+            let mut grid = FluidGrid::new(layout.width, layout.height);
+            grid.set_viscocity(self.viscocity).unwrap();
+            if let Some(capacity) = self.capacity {
+                grid.set_capacity(capacity).unwrap();
+            }
+            for (pos, fluid) in layout.points().zip(self.fluid.iter()) {
+                if !layout.is_solid(pos) {
+                    grid.set_fluid(pos, *fluid).unwrap();
+                }
+            }
+            grid
+        }
+    }
+
+    fn neighbourhood() -> impl Strategy<Value = Neighbourhood> {
+        prop_oneof![
+            Just(Neighbourhood::VonNeumann),
+            Just(Neighbourhood::Moore),
+            Just(Neighbourhood::HexOffset),
+        ]
+    }
+
+    /*
+     * Most connections are left fully open, shrinking opens the rest
+     */
+    fn throttle() -> impl Strategy<Value = f32> {
+        prop_oneof![
+            4 => Just(1f32),
+            1 => Just(0f32),
+            1 => 0f32..1f32,
+        ]
+    }
+
+    fn case(throttled: bool) -> impl Strategy<Value = Case> {
+        (2usize..10, 2usize..10, neighbourhood())
+            .prop_flat_map(move |(width, height, neighbourhood)| {
+                let connections = width * height * neighbourhood.size();
+                let throttle = if throttled { vec(throttle(), connections).boxed() } else { Just(vec![1f32; connections]).boxed() };
+                (
+                    vec(prop::bool::weighted(0.3), width * height),
+                    throttle,
+                    vec(0f32..10f32, width * height),
+                    0f32..1f32,
+                    prop::option::weighted(0.2, 0.5f32..5f32),
+                    any::<bool>(),
+                    Just((width, height, neighbourhood)),
+                )
+            })
+            .prop_map(|(solid, throttle, fluid, viscocity, capacity, leaky, (width, height, neighbourhood))| Case {
+                layout: Layout {
+                    width,
+                    height,
+                    solid,
+                    throttle,
+                    neighbourhood,
+                    leaky,
+                },
+                fluid,
+                viscocity,
+                capacity,
+            })
+    }
+
+    /*
+     * Layouts and fluid mirrored left to right, the width stays below the chunk
+     * size so both halves always sleep and wake together
+     */
+    fn mirrored() -> impl Strategy<Value = Case> {
+        (1usize..6, 2usize..10, any::<bool>(), prop_oneof![Just(Neighbourhood::VonNeumann), Just(Neighbourhood::Moore)])
+            .prop_flat_map(|(half, height, odd, neighbourhood)| {
+                (
+                    vec(prop::bool::weighted(0.3), half * height),
+                    vec(0f32..10f32, half * height),
+                    0f32..1f32,
+                    Just((half, height, odd, neighbourhood)),
+                )
+            })
+            .prop_map(|(solid, fluid, viscocity, (half, height, odd, neighbourhood))| {
+                let width = half * 2 + odd as usize;
+                let mirror = |i: usize| {
+                    let (x, y) = (i % width, i / width);
+                    (x.min(width - 1 - x).min(half - 1), y)
+                };
+                let solid = (0..width * height)
+                    .map(|i| {
+                        let (x, y) = mirror(i);
+                        solid[x + y * half] && !(odd && i % width == half)
+                    })
+                    .collect();
+                let fluid = (0..width * height)
+                    .map(|i| {
+                        let (x, y) = mirror(i);
+                        fluid[x + y * half]
+                    })
+                    .collect();
+                let connections = width * height * neighbourhood.size();
+                Case {
+                    layout: Layout {
+                        width,
+                        height,
+                        solid,
+                        throttle: vec![1f32; connections],
+                        neighbourhood,
+                        leaky: false,
+                    },
+                    fluid,
+                    viscocity,
+                    capacity: None,
+                }
+            })
+    }
+
+    fn tolerance(total: f32) -> f32 {
+        0.001 * total.max(1f32)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        #[test]
+        fn fluid_stays_finite_and_positive(case in case(true)) {
+            let mut grid = case.grid();
+            for step in 0..STEPS {
+                grid.flow(&case.layout);
+                for cell in grid.iter_cells() {
+                    prop_assert!(cell.fluid.is_finite() && cell.fluid >= 0f32, "fluid {} at {:?} after step {}", cell.fluid, cell.position, step);
+                    prop_assert!(cell.pressure.is_finite(), "pressure {} at {:?} after step {}", cell.pressure, cell.position, step);
+                    prop_assert!(cell.velocity.x.is_finite() && cell.velocity.y.is_finite(), "velocity at {:?} after step {}", cell.position, step);
+                }
+            }
+        }
+
+        #[test]
+        fn fluid_is_conserved(case in case(true)) {
+            let mut grid = case.grid();
+            let total = grid.total_fluid_level();
+            for step in 0..STEPS {
+                grid.flow(&case.layout);
+                let now = grid.total_fluid_level();
+                prop_assert!((now - total).abs() < tolerance(total), "total went from {} to {} after step {}", total, now, step);
+            }
+        }
+
+        #[test]
+        fn solid_cells_stay_empty(case in case(true)) {
+            let mut grid = case.grid();
+            for step in 0..STEPS {
+                grid.flow(&case.layout);
+                for pos in case.layout.points().filter(|pos| case.layout.is_solid(*pos)) {
+                    prop_assert_eq!(grid.get_fluid(pos), Some(&0f32), "fluid in solid cell {:?} after step {}", pos, step);
+                }
+            }
+        }
+
+        #[test]
+        fn mirrored_layouts_stay_mirrored(case in mirrored()) {
+            let mut grid = case.grid();
+            let width = case.layout.width;
+            let total = grid.total_fluid_level();
+            for step in 0..STEPS {
+                grid.flow(&case.layout);
+                for pos in case.layout.points() {
+                    let left = *grid.get_fluid(pos).unwrap();
+                    let right = *grid.get_fluid([width - 1 - pos.x, pos.y]).unwrap();
+                    prop_assert!((left - right).abs() < tolerance(total), "{:?} holds {} and its mirror {} after step {}", pos, left, right, step);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+#[cfg(test)]
+mod queries {
+    use fluid_dynamics::{FluidGrid, Rect, SolidGrid, Source, SourceKind};
+
+    use crate::common::{assert_fluid_eq};
+
+    fn rooms() -> SolidGrid {
+        "
+            ...#..
+            ...#..
+            ......
+            ####..
+        ".parse().unwrap()
+    }
+
+    #[test]
+    fn fluid_per_room() {
+        let mut grid = FluidGrid::new(6, 4);
+        let mut g = rooms();
+        grid.set_fluid([0, 0], 2f32).unwrap();
+        grid.set_fluid([5, 3], 3f32).unwrap();
+
+        assert_eq!(grid.connected_cells([1, 1], &g).len(), 18);
+        assert_fluid_eq(grid.region_fluid([1, 1], &g).unwrap(), 5f32);
+        assert_eq!(grid.region_fluid([3, 0], &g), None);
+        assert_eq!(grid.region_fluid([6, 0], &g), None);
+
+        g.set_solid([3, 2], true);
+        assert_eq!(grid.connected_cells([0, 0], &g)[0], [0, 0].into());
+        assert_fluid_eq(grid.region_fluid([0, 0], &g).unwrap(), 2f32);
+        assert_fluid_eq(grid.region_fluid([4, 0], &g).unwrap(), 3f32);
+
+        g.set_solid([3, 2], false);
+        g.set_one_way([4, 2], [3, 2]);
+        assert_fluid_eq(grid.region_fluid([4, 0], &g).unwrap(), 5f32);
+        assert_fluid_eq(grid.region_fluid([0, 0], &g).unwrap(), 2f32);
+    }
+
+    #[test]
+    fn flood_extent() {
+        let mut grid = FluidGrid::new(6, 4);
+        grid.set_fluid([1, 1], 2f32).unwrap();
+        grid.set_fluid([4, 2], 0.5).unwrap();
+        grid.set_fluid([2, 3], 1f32).unwrap();
+
+        assert_eq!(grid.cells_above(0.75), vec![[1, 1].into(), [2, 3].into()]);
+        assert_eq!(grid.bounds_above(0.75), Some(Rect::new(1, 1, 2, 3)));
+        assert_eq!(grid.bounds_above(0f32), Some(Rect::new(1, 1, 4, 3)));
+        assert_eq!(grid.bounds_above(5f32), None);
+    }
+
+    #[test]
+    fn gradient_points_towards_fluid() {
+        let mut grid = FluidGrid::new(3, 3);
+        let g: SolidGrid = "
+            ...
+            ...
+            .#.
+        ".parse().unwrap();
+        grid.set_fluid([2, 1], 4f32).unwrap();
+        grid.set_fluid([0, 1], 2f32).unwrap();
+        grid.set_fluid([1, 0], 1f32).unwrap();
+
+        let gradient = grid.get_gradient([1, 1], &g).unwrap();
+        assert_fluid_eq(gradient.x, 1f32);
+        assert_fluid_eq(gradient.y, -1f32);
+
+        let gradient = grid.get_gradient([0, 0], &g).unwrap();
+        assert_fluid_eq(gradient.x, 1f32);
+        assert_fluid_eq(gradient.y, 2f32);
+
+        assert_eq!(grid.get_gradient([1, 2], &g), Some([0f32, 0f32].into()));
+        assert_eq!(grid.get_gradient([3, 0], &g), None);
+    }
+
+    #[test]
+    fn predicted_steps_until_a_level() {
+        let mut grid = FluidGrid::new(8, 1);
+        let g = SolidGrid::new(8, 1);
+        grid.set_fluid([0, 0], 16f32).unwrap();
+
+        let steps = grid.steps_until([4, 0], 1f32, 100, &g).unwrap();
+        assert!(steps > 0);
+        assert_eq!(grid.get_fluid([0, 0]), Some(&16f32));
+        assert_eq!(grid.steps_until([0, 0], 1f32, 100, &g), Some(0));
+        assert_eq!(grid.steps_until([4, 0], 3f32, 1000, &g), None);
+
+        let mut fork = grid.clone();
+        fork.step(steps - 1, &g);
+        assert!(*fork.get_fluid([4, 0]).unwrap() < 1f32);
+        fork.flow(&g);
+        assert!(*fork.get_fluid([4, 0]).unwrap() >= 1f32);
+
+        grid.add_source(Source::emitter([7, 0], SourceKind::Constant { rate: 1f32 })).unwrap();
+        assert!(grid.steps_until([4, 0], 3f32, 1000, &g).is_some());
+    }
+}
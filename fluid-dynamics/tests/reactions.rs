@@ -0,0 +1,123 @@
+mod common;
+
+#[cfg(test)]
+mod reactions {
+    use fluid_dynamics::{FluidGrid, Reaction, ReactionTable, SolidGrid, Species, SpeciesId, Temperature};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn water_dilutes_acid() {
+        let mut grid = FluidGrid::new(3, 1);
+        let g = SolidGrid::new(3, 1);
+        let acid = grid.add_species(Species::new("acid", 1f32, 1f32)).unwrap();
+        let water = SpeciesId::default();
+        grid.set_species_fluid(acid, [0, 0], 3f32).unwrap();
+        grid.set_fluid([0, 0], 1f32).unwrap();
+        grid.set_species_fluid(acid, [2, 0], 3f32).unwrap();
+
+        let mut table = ReactionTable::new();
+        let dilute = table.add(Reaction::new(vec![(acid, 1f32), (water, 1f32)], vec![(water, 2f32)], 0.5)).unwrap();
+        let events = table.react(&mut grid, &g, 1f32);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].reaction, dilute);
+        assert_eq!(events[0].position, [0, 0].into());
+        assert_fluid_eq(events[0].amount, 0.5);
+        assert_fluid_eq(*grid.get_species_fluid(acid, [0, 0]).unwrap(), 2.5);
+        assert_fluid_eq(*grid.get_fluid([0, 0]).unwrap(), 1.5);
+        assert_eq!(grid.get_species_fluid(acid, [2, 0]), Some(&3f32));
+    }
+
+    #[test]
+    fn corrosion_needs_a_surface() {
+        let mut grid = FluidGrid::new(4, 3);
+        let g: SolidGrid = "
+            0000
+            0001
+            0000
+        ".parse().unwrap();
+        grid.set_fluid([0, 0], 2f32).unwrap();
+        grid.set_fluid([2, 1], 2f32).unwrap();
+
+        let mut table = ReactionTable::new();
+        table.add(Reaction::new(vec![(SpeciesId::default(), 1f32)], vec![], 1f32).on_surface().with_damage(10f32)).unwrap();
+        let events = table.react(&mut grid, &g, 0.5);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].surface, Some([3, 1].into()));
+        assert_fluid_eq(events[0].damage, 10f32);
+        assert_eq!(grid.get_fluid([0, 0]), Some(&2f32));
+        assert_fluid_eq(grid.total_fluid_level(), 3f32);
+    }
+
+    #[test]
+    fn explosions_need_heat() {
+        let mut grid = FluidGrid::new(3, 1);
+        let g = SolidGrid::new(3, 1);
+        grid.enable_temperature(Temperature::default()).unwrap();
+        grid.set_fluid([0, 0], 4f32).unwrap();
+        grid.set_fluid([2, 0], 4f32).unwrap();
+        grid.set_temperature([2, 0], 300f32).unwrap();
+
+        let mut table = ReactionTable::new();
+        let explosion = Reaction::new(vec![(SpeciesId::default(), 1f32)], vec![], 1f32)
+            .above(200f32)
+            .with_heat(50f32)
+            .with_pressure(25f32);
+        table.add(explosion).unwrap();
+        let events = table.react(&mut grid, &g, 1f32);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].position, [2, 0].into());
+        assert_fluid_eq(events[0].pressure, 100f32);
+        assert_fluid_eq(grid.get_temperature([2, 0]).unwrap(), 500f32);
+        assert_eq!(grid.get_fluid([2, 0]), Some(&0f32));
+        assert!(table.react(&mut grid, &g, 1f32).is_empty());
+
+        grid.disable_temperature();
+        assert!(table.react(&mut grid, &g, 1f32).is_empty());
+        assert_eq!(grid.get_fluid([0, 0]), Some(&4f32));
+    }
+
+    #[test]
+    fn bad_reactions_are_skipped_or_rejected() {
+        let mut grid = FluidGrid::new(2, 1);
+        let g = SolidGrid::new(2, 1);
+        grid.set_fluid([0, 0], 1f32).unwrap();
+        let other = FluidGrid::with_species(1, 1, vec![Species::default(), Species::new("gas", 1f32, 0.1)]);
+        let gas = other.species_id("gas").unwrap();
+
+        let mut table = ReactionTable::new();
+        assert!(table.add(Reaction::new(vec![(SpeciesId::default(), f32::NAN)], vec![], 1f32)).is_err());
+        assert!(table.add(Reaction::new(vec![(SpeciesId::default(), 1f32)], vec![], -1f32)).is_err());
+        table.add(Reaction::new(vec![(SpeciesId::default(), 1f32)], vec![(gas, 1f32)], 1f32)).unwrap();
+        table.add(Reaction::new(vec![], vec![(SpeciesId::default(), 1f32)], 1f32)).unwrap();
+
+        assert!(table.react(&mut grid, &g, 1f32).is_empty());
+        assert_eq!(grid.total_fluid_level(), 1f32);
+    }
+
+    #[test]
+    fn resting_fluid_still_reacts() {
+        let mut grid = FluidGrid::new(9, 1);
+        let mut g: SolidGrid = "000010000".parse().unwrap();
+        grid.set_chunk_size(3).unwrap();
+        grid.set_fluid([0, 0], 2f32).unwrap();
+        for _ in 0..500 {
+            grid.flow(&g);
+        }
+        assert!(grid.is_stable());
+
+        let mut table = ReactionTable::new();
+        table.add(Reaction::new(vec![(SpeciesId::default(), 1f32)], vec![], 1f32).on_surface()).unwrap();
+        let events = table.react(&mut grid, &g, 0.5);
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].position, events[0].surface), ([3, 0].into(), Some([4, 0].into())));
+
+        g.set_solid([1, 0], true);
+        grid.invalidate_cell([1, 0]);
+        let events = table.react(&mut grid, &g, 0.5);
+        assert_eq!(events.iter().filter(|event| event.surface == Some([1, 0].into())).count(), 2);
+    }
+}
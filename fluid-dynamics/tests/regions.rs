@@ -0,0 +1,115 @@
+mod common;
+
+#[cfg(test)]
+mod regions {
+    use fluid_dynamics::{FluidError, FluidGrid, SolidGrid};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn empty_grid_is_asleep() {
+        let grid = FluidGrid::new(40, 40);
+
+        assert!(grid.is_stable());
+        assert_eq!(grid.active_regions().count(), 0);
+        assert_eq!(grid.is_region_stable([39, 39]), Some(true));
+        assert_eq!(grid.is_region_stable([40, 0]), None);
+    }
+
+    #[test]
+    fn only_regions_near_fluid_wake_up() {
+        let mut grid = FluidGrid::new(64, 64);
+        let g = SolidGrid::new(64, 64);
+        assert_eq!(grid.chunk_size(), 16);
+
+        grid.set_fluid([3, 3], 10f32).unwrap();
+        assert_eq!(grid.active_regions().collect::<Vec<_>>(), vec![[0, 0].into()]);
+
+        grid.step(3, &g);
+        assert!(!grid.is_stable());
+        assert_eq!(grid.is_region_stable([3, 3]), Some(false));
+        assert_eq!(grid.is_region_stable([40, 40]), Some(true));
+        assert_eq!(grid.is_region_stable([20, 3]), Some(true));
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+    }
+
+    #[test]
+    fn fluid_crosses_into_sleeping_regions() {
+        let mut grid = FluidGrid::new(8, 4);
+        grid.set_chunk_size(4).unwrap();
+        let g = SolidGrid::new(8, 4);
+
+        for y in 0..4 {
+            for x in 0..4 {
+                grid.set_fluid([x, y], 2f32).unwrap();
+            }
+        }
+        for _ in 0..1000 {
+            grid.flow(&g);
+            if grid.is_stable() {
+                break;
+            }
+        }
+
+        assert!(grid.is_stable());
+        assert_fluid_eq(*grid.get_fluid([0, 0]).unwrap(), 1f32);
+        assert_fluid_eq(*grid.get_fluid([7, 3]).unwrap(), 1f32);
+        assert_fluid_eq(grid.total_fluid_level(), 32f32);
+    }
+
+    #[test]
+    fn changes_wake_sleeping_regions() {
+        let mut grid = FluidGrid::new(32, 32);
+        let g = SolidGrid::new(32, 32);
+
+        grid.flow(&g);
+        assert!(grid.is_stable());
+
+        grid.add_fluid([20, 20], 5f32).unwrap();
+        assert_eq!(grid.is_region_stable([20, 20]), Some(false));
+        assert_eq!(grid.active_regions().collect::<Vec<_>>(), vec![[16, 16].into()]);
+
+        grid.flow(&g);
+        assert!(*grid.get_fluid([21, 20]).unwrap() > 0f32);
+    }
+
+    #[test]
+    fn chunk_sizes_are_checked() {
+        let g = SolidGrid::new(40, 14);
+        let mut chunked = FluidGrid::new(40, 14);
+        chunked.set_fluid([3, 3], 10f32).unwrap();
+        let mut whole = FluidGrid::new(40, 14);
+        whole.set_fluid([3, 3], 10f32).unwrap();
+
+        assert_eq!(whole.set_chunk_size(0), Err(FluidError::InvalidChunkSize(0)));
+        assert_eq!(whole.chunk_size(), 16);
+        whole.set_chunk_size(1 << 16).unwrap();
+        assert_eq!(whole.active_regions().collect::<Vec<_>>(), vec![[0, 0].into()]);
+
+        chunked.step(20, &g);
+        whole.step(20, &g);
+        for (a, b) in chunked.iter().zip(whole.iter()) {
+            assert_fluid_eq(a.1, b.1);
+        }
+
+        whole.set_chunk_size(usize::MAX).unwrap();
+        whole.step(2, &g);
+        assert_fluid_eq(whole.total_fluid_level(), 10f32);
+    }
+
+    #[test]
+    fn grids_without_cells_still_step() {
+        for (width, height) in [(5, 0), (0, 5), (0, 0)].iter() {
+            let mut grid = FluidGrid::new(*width, *height);
+            let report = grid.step(3, &SolidGrid::new(*width, *height));
+            assert_eq!(report.steps, 3);
+            assert!(grid.is_stable());
+        }
+
+        let mut grid = FluidGrid::new(4, 4);
+        grid.set_fluid([1, 1], 2f32).unwrap();
+        grid.resize(0, 4);
+        grid.flow(&SolidGrid::new(0, 4));
+        assert_eq!(grid.total_fluid_level(), 0f32);
+    }
+}
@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod render {
+    use fluid_dynamics::{ColourRamp, Field, FluidGrid, HeatMap, SolidGrid, Species};
+
+    #[test]
+    fn ramps_blend_between_stops() {
+        let ramp = ColourRamp::new(vec![(1f32, [200, 100, 0]), (0f32, [0, 0, 0]), (f32::NAN, [1, 2, 3])]);
+
+        assert_eq!(ramp.stops().len(), 2);
+        assert_eq!(ramp.sample(0f32), [0, 0, 0]);
+        assert_eq!(ramp.sample(0.5), [100, 50, 0]);
+        assert_eq!(ramp.sample(7f32), [200, 100, 0]);
+        assert_eq!(ramp.sample(f32::NAN), [0, 0, 0]);
+        assert_eq!(ColourRamp::heat().sample(1f32), [255, 255, 255]);
+        assert_eq!(ColourRamp::evenly(&[[9, 9, 9]]).sample(0.5), [9, 9, 9]);
+    }
+
+    #[test]
+    fn heat_map_with_walls() {
+        let mut grid = FluidGrid::new(3, 2);
+        let g: SolidGrid = "
+            ..#
+            ...
+        ".parse().unwrap();
+        grid.set_fluid([0, 0], 4f32).unwrap();
+        grid.set_fluid([1, 0], 2f32).unwrap();
+
+        let heat_map = HeatMap::new(Field::Fluid)
+            .with_ramp(ColourRamp::grey())
+            .with_scale(2)
+            .with_wall_colour([0, 0, 255]);
+        let frame = heat_map.render_with_walls(&grid, &g);
+        assert_eq!((frame.width(), frame.height()), (6, 4));
+        assert_eq!(frame.pixel(1, 1), Some([255, 255, 255]));
+        assert_eq!(frame.pixel(2, 0), Some([128, 128, 128]));
+        assert_eq!(frame.pixel(5, 1), Some([0, 0, 255]));
+        assert_eq!(frame.pixel(5, 3), Some([0, 0, 0]));
+        assert_eq!(frame.pixel(6, 0), None);
+
+        assert_eq!(heat_map.scale(), 2);
+        assert_eq!(heat_map.clone().with_scale(0).scale(), 1);
+        let frame = heat_map.with_range(0f32, 8f32).render(&grid);
+        assert_eq!(frame.pixel(0, 0), Some([128, 128, 128]));
+        assert_eq!(frame.pixel(4, 0), Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn fields_and_ranges() {
+        let mut grid = FluidGrid::with_species(2, 1, vec![Species::default(), Species::new("gas", 1f32, 0.1)]);
+        let gas = grid.species_id("gas").unwrap();
+        grid.set_species_fluid(gas, [1, 0], 3f32).unwrap();
+        grid.set_fluid([0, 0], 1f32).unwrap();
+
+        assert_eq!(HeatMap::new(Field::Fluid).range(&grid), (0f32, 3f32));
+        assert_eq!(HeatMap::new(Field::Species(gas)).range(&grid), (0f32, 3f32));
+        assert_eq!(HeatMap::new(Field::Velocity).range(&grid), (0f32, 0f32));
+        assert_eq!(HeatMap::new(Field::Pressure).with_range(-1f32, 1f32).range(&grid), (-1f32, 1f32));
+
+        let frame = HeatMap::new(Field::Velocity).render(&grid);
+        assert_eq!(frame.pixel(1, 0), Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn ppm_output() {
+        let mut grid = FluidGrid::new(2, 1);
+        grid.set_fluid([1, 0], 1f32).unwrap();
+        let mut bytes = vec![];
+        HeatMap::new(Field::Fluid).render(&grid).write_ppm(&mut bytes).unwrap();
+
+        assert_eq!(bytes, b"P6\n2 1\n255\n\x00\x00\x00\xff\xff\xff".to_vec());
+    }
+}
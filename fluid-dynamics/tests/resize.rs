@@ -0,0 +1,85 @@
+mod common;
+
+#[cfg(test)]
+mod resize {
+    use fluid_dynamics::{FluidGrid, Rect, SolidGrid, Species};
+
+    use crate::common::{assert_fluid_eq, flowing_grid};
+
+    #[test]
+    fn rect_intersection() {
+        let rect = Rect::new(2, 2, 4, 3);
+        assert_eq!(rect.intersection(&Rect::new(0, 0, 4, 4)), Rect::new(2, 2, 2, 2));
+        assert!(rect.intersection(&Rect::new(6, 0, 2, 8)).is_empty());
+        assert!(rect.contains([5, 4]));
+        assert!(!rect.contains([6, 4]));
+        assert_eq!(rect.points().count(), rect.area());
+    }
+
+    #[test]
+    fn resize_keeps_overlapping_cells() {
+        let mut grid = flowing_grid(6, 4, 3, |_| {});
+        let fluid = *grid.get_fluid([1, 1]).unwrap();
+        let velocity = *grid.get_velocity([2, 1]).unwrap();
+        let pressure = *grid.get_pressure([1, 2]).unwrap();
+
+        grid.resize(9, 3);
+        assert_eq!(grid.dimensions(), [9, 3].into());
+        assert_eq!(grid.get_fluid([1, 1]), Some(&fluid));
+        assert_eq!(grid.get_velocity([2, 1]), Some(&velocity));
+        assert_eq!(grid.get_pressure([1, 2]), Some(&pressure));
+        assert_eq!(grid.get_fluid([8, 2]), Some(&0f32));
+        assert_eq!(grid.get_fluid([1, 3]), None);
+
+        let total = grid.total_fluid_level();
+        grid.step(5, &SolidGrid::new(9, 3));
+        assert_fluid_eq(grid.total_fluid_level(), total);
+        assert!(*grid.get_fluid([6, 1]).unwrap() > 0f32);
+    }
+
+    #[test]
+    fn sub_grid_copies_region() {
+        let grid = flowing_grid(6, 4, 3, |_| {});
+        let sub = grid.sub_grid(Rect::new(1, 1, 8, 2));
+
+        assert_eq!(sub.dimensions(), [5, 2].into());
+        for pos in Rect::new(1, 1, 5, 2).points() {
+            let local = [pos.x - 1, pos.y - 1];
+            assert_eq!(sub.get_fluid(local), grid.get_fluid(pos));
+            assert_eq!(sub.get_velocity(local), grid.get_velocity(pos));
+            assert_eq!(sub.get_pressure(local), grid.get_pressure(pos));
+        }
+        assert!(!sub.is_stable());
+    }
+
+    #[test]
+    fn blit_matches_species_by_name() {
+        let mut from = FluidGrid::with_species(3, 3, vec![Species::new("gas", 0.5, 0.2)]);
+        from.set_fluid([1, 1], 4f32).unwrap();
+        let mut to = FluidGrid::new(5, 5);
+        let water = to.add_species(Species::new("water", 0.3, 1f32)).unwrap();
+        to.set_fluid([4, 4], 2f32).unwrap();
+        to.set_species_fluid(water, [4, 3], 1f32).unwrap();
+
+        let written = to.blit_from(&from, Rect::new(0, 0, 3, 3), [3, 2]);
+        assert_eq!(written, Rect::new(3, 2, 2, 3));
+
+        let gas = to.species_id("gas").unwrap();
+        assert_eq!(to.get_species_fluid(gas, [4, 3]), Some(&4f32));
+        assert_eq!(to.get_species_fluid(water, [4, 3]), Some(&0f32));
+        assert_eq!(to.get_fluid([4, 4]), Some(&0f32));
+        assert_eq!(to.get_species_fluid(gas, [0, 0]), Some(&0f32));
+    }
+
+    #[test]
+    fn blit_outside_does_nothing() {
+        let from = flowing_grid(6, 4, 3, |_| {});
+        let mut to = FluidGrid::new(4, 4);
+        assert!(to.blit_from(&from, Rect::new(0, 0, 2, 2), [4, 0]).is_empty());
+        assert!(to.blit_from(&from, Rect::new(6, 0, 2, 2), [0, 0]).is_empty());
+        assert!(to.blit_from(&from, Rect::new(0, 0, 2, 2), [usize::MAX, 0]).is_empty());
+        assert!(to.blit_from(&from, Rect::new(usize::MAX - 1, 0, 5, 5), [0, 0]).is_empty());
+        assert_eq!(to.total_fluid_level(), 0f32);
+        assert!(to.is_stable());
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(all(test, feature = "serde"))]
+mod save {
+    use fluid_dynamics::{FluidGrid, FluidSave, HeatSource, SaveError, SolidGrid, Source, SourceKind, Species, Temperature};
+
+    fn assert_same(a: &FluidGrid, b: &FluidGrid) {
+        assert_eq!(a.dimensions(), b.dimensions());
+        for (species, _) in a.species() {
+            let (a, b) = (a.iter_species(species), b.iter_species(species));
+            for ((pos, fa), (_, fb)) in a.zip(b) {
+                assert_eq!(fa.to_bits(), fb.to_bits(), "fluid differs at {:?}", pos);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_is_bit_exact() {
+        let g: SolidGrid = "
+            00000000
+            00100100
+            00100000
+        ".parse().unwrap();
+        let mut grid = FluidGrid::new(8, 3);
+        let gas = grid.add_species(Species::new("gas", 0.7, 0.3)).unwrap();
+        grid.set_fluid([0, 0], 13.7f32).unwrap();
+        grid.set_species_fluid(gas, [7, 2], 5.1f32).unwrap();
+        grid.add_source(Source::emitter([1, 2], SourceKind::Pulsed { amount: 3f32, interval: 2 })).unwrap();
+        grid.enable_temperature(Temperature::default()).unwrap();
+        grid.add_heat_source(HeatSource::new([6, 0], 300f32, 0.5)).unwrap();
+        grid.step(7, &g);
+
+        let json = serde_json::to_string(&grid).unwrap();
+        let mut loaded: FluidGrid = serde_json::from_str(&json).unwrap();
+        assert_same(&grid, &loaded);
+        assert_eq!(loaded.sources().count(), 1);
+        assert_eq!(loaded.get_velocity([3, 0]), grid.get_velocity([3, 0]));
+        assert_eq!(loaded.get_pressure([2, 1]), grid.get_pressure([2, 1]));
+        assert_eq!(loaded.get_temperature([5, 0]), grid.get_temperature([5, 0]));
+        assert_eq!(loaded.heat_sources().count(), 1);
+
+        grid.step(5, &g);
+        loaded.step(5, &g);
+        assert_same(&grid, &loaded);
+    }
+
+    #[test]
+    fn version_one_is_migrated() {
+        let json = r#"{
+            "version": 1,
+            "width": 2,
+            "height": 2,
+            "viscocity": 0.5,
+            "fluid": [4.0, 0.0, 0.0, 1.0],
+            "pressure": [4.0, 0.0, 0.0, 1.0],
+            "velocity": [[1, 0], [0, 0], [0, 0], [0, -1]]
+        }"#;
+        let grid: FluidGrid = serde_json::from_str(json).unwrap();
+
+        assert_eq!(grid.species().count(), 1);
+        assert_eq!(grid.get_species(Default::default()).unwrap().viscocity, 0.5);
+        assert_eq!(grid.get_fluid([0, 0]), Some(&4f32));
+        assert_eq!(grid.get_fluid([1, 1]), Some(&1f32));
+        assert_eq!(grid.get_velocity([1, 1]).unwrap().y, -1f32);
+        assert!(!grid.is_stable());
+    }
+
+    #[test]
+    fn bad_saves_are_rejected() {
+        let mut save = FluidGrid::new(3, 3).to_save();
+        save.version = 99;
+        assert_eq!(FluidGrid::from_save(save.clone()).err(), Some(SaveError::UnsupportedVersion(99)));
+
+        let mut save: FluidSave = FluidGrid::new(3, 3).to_save();
+        save.pressure.pop();
+        assert_eq!(FluidGrid::from_save(save).err(), Some(SaveError::WrongSize("pressure")));
+
+        let mut save = FluidGrid::new(3, 3).to_save();
+        save.pressure[4] = f32::NAN;
+        assert_eq!(FluidGrid::from_save(save).err(), Some(SaveError::Invalid("pressure")));
+
+        for chunk_size in [0, 4, 1 << 40].iter() {
+            let mut save = FluidGrid::new(3, 3).to_save();
+            save.simulation.as_mut().unwrap().chunk_size = *chunk_size;
+            assert_eq!(FluidGrid::from_save(save).err(), Some(SaveError::Invalid("simulation")));
+        }
+        let mut save = FluidGrid::new(3, 3).to_save();
+        save.simulation.as_mut().unwrap().timestep = 0f32;
+        assert_eq!(FluidGrid::from_save(save).err(), Some(SaveError::Invalid("simulation")));
+
+        let mut save = FluidGrid::new(3, 3).to_save();
+        save.width = usize::MAX;
+        assert_eq!(FluidGrid::from_save(save).err(), Some(SaveError::WrongSize("width")));
+
+        let mut grid = FluidGrid::new(3, 3);
+        grid.set_chunk_size(1 << 16).unwrap();
+        assert_eq!(FluidGrid::from_save(grid.to_save()).unwrap().chunk_size(), 3);
+
+        let json = r#"{"version": 1, "width": 1, "height": 1, "pressure": [0.0], "velocity": [[0, 0]]}"#;
+        let error = serde_json::from_str::<FluidGrid>(json).err().unwrap();
+        assert!(error.to_string().contains("missing fluid"));
+    }
+}
@@ -0,0 +1,193 @@
+#[cfg(test)]
+mod snapshots {
+    use std::fs;
+    use std::path::{PathBuf};
+
+    use fluid_dynamics::{FluidGrid, SolidGrid, Source, SourceKind, Species, SpeciesId};
+
+    /*
+     * Scenarios are text fixtures in tests/scenarios, one setting per line
+     * followed by the map after a line reading `map`. Every step of a scenario
+     * is recorded and compared with tests/golden, run with UPDATE_GOLDEN=1 to
+     * write the golden files again after an intended change to the solver
+     */
+    struct Scenario {
+        grid: FluidGrid,
+        walls: SolidGrid,
+        steps: u32,
+    }
+
+    fn path(dir: &str, name: &str, extension: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", dir, &format!("{}.{}", name, extension)].iter().collect()
+    }
+
+    fn species(grid: &FluidGrid, name: &str) -> SpeciesId {
+        grid.species_id(name).unwrap_or_else(|| panic!("unknown species {}", name))
+    }
+
+    fn load(name: &str) -> Scenario {
+        let text = fs::read_to_string(path("scenarios", name, "scenario")).unwrap();
+        let (settings, map) = text.split_once("\nmap\n").expect("scenario has no map");
+        let mut walls: SolidGrid = map.parse().expect("bad map");
+        let size = walls.dimensions();
+        let mut grid = FluidGrid::new(size.x, size.y);
+        let mut steps = 1;
+
+        for line in settings.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let number = |i: usize| -> f32 { words[i].parse().unwrap_or_else(|_| panic!("bad number in {}", line)) };
+            let cell = |i: usize| -> [usize; 2] { [number(i) as usize, number(i + 1) as usize] };
+            match words[0] {
+                "steps" => steps = number(1) as u32,
+                "viscocity" => grid.set_viscocity(number(1)).unwrap(),
+                "substeps" => grid.set_substeps(number(1) as u32),
+                "capacity" => grid.set_capacity(number(1)).unwrap(),
+                "species" => {
+                    grid.add_species(Species::new(words[1], number(2), number(3))).unwrap();
+                },
+                "fluid" => {
+                    let species = species(&grid, words[1]);
+                    grid.set_species_fluid(species, cell(2), number(4)).unwrap();
+                },
+                "emitter" | "drain" => {
+                    let kind = SourceKind::Constant { rate: number(4) };
+                    let source = if words[0] == "drain" {
+                        Source::drain(cell(2), kind)
+                    } else {
+                        Source::emitter(cell(2), kind)
+                    };
+                    grid.add_source(source.with_species(species(&grid, words[1]))).unwrap();
+                },
+                "conductance" => walls.set_conductance(cell(1), cell(3), number(5)),
+                "one_way" => walls.set_one_way(cell(1), cell(3)),
+                _ => panic!("unknown setting {}", line),
+            }
+        }
+        Scenario {
+            grid,
+            walls,
+            steps,
+        }
+    }
+
+    /*
+     * Values are rounded to four decimals and trailing zeros dropped
+     */
+    fn number(value: f32) -> String {
+        let text = format!("{:.4}", value);
+        let text = text.trim_end_matches('0').trim_end_matches('.');
+        if text == "-0" { "0".to_string() } else { text.to_string() }
+    }
+
+    fn record(grid: &FluidGrid, step: u32, out: &mut String) {
+        let size = grid.dimensions();
+        for (species, info) in grid.species() {
+            out.push_str(&format!("step {} fluid {}\n", step, info.name));
+            for y in 0..size.y {
+                let row = (0..size.x)
+                    .map(|x| number(*grid.get_species_fluid(species, [x, y]).unwrap()))
+                    .collect::<Vec<_>>();
+                out.push_str(&row.join(" "));
+                out.push('\n');
+            }
+        }
+        out.push_str(&format!("step {} pressure\n", step));
+        for y in 0..size.y {
+            let row = (0..size.x)
+                .map(|x| number(*grid.get_pressure([x, y]).unwrap()))
+                .collect::<Vec<_>>();
+            out.push_str(&row.join(" "));
+            out.push('\n');
+        }
+        out.push_str(&format!("step {} velocity\n", step));
+        for y in 0..size.y {
+            let row = (0..size.x)
+                .map(|x| grid.get_velocity([x, y]).unwrap())
+                .map(|v| format!("{},{}", number(v.x), number(v.y)))
+                .collect::<Vec<_>>();
+            out.push_str(&row.join(" "));
+            out.push('\n');
+        }
+    }
+
+    fn run(name: &str) -> String {
+        let Scenario { mut grid, walls, steps } = load(name);
+        let mut out = String::new();
+        record(&grid, 0, &mut out);
+        for step in 1..=steps {
+            grid.flow(&walls);
+            record(&grid, step, &mut out);
+        }
+        out
+    }
+
+    /*
+     * Lists every cell that differs, under the step and field it belongs to
+     */
+    fn diverged(expected: &str, actual: &str) -> Vec<String> {
+        let mut differences = vec![];
+        let mut section = "";
+        let mut y = 0;
+        for (want, got) in expected.lines().zip(actual.lines()) {
+            if want.starts_with("step") || got.starts_with("step") {
+                if want != got {
+                    differences.push(format!("expected section '{}', found '{}'", want, got));
+                    break;
+                }
+                section = want;
+                y = 0;
+                continue;
+            }
+            for (x, (a, b)) in want.split(' ').zip(got.split(' ')).enumerate() {
+                if a != b {
+                    differences.push(format!("{} at {},{}: expected {} found {}", section, x, y, a, b));
+                }
+            }
+            y += 1;
+        }
+        if expected.lines().count() != actual.lines().count() {
+            differences.push(format!("expected {} lines, found {}", expected.lines().count(), actual.lines().count()));
+        }
+        differences
+    }
+
+    fn check(name: &str) {
+        let actual = run(name);
+        let golden = path("golden", name, "snap");
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(golden.parent().unwrap()).unwrap();
+            fs::write(&golden, &actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&golden)
+            .unwrap_or_else(|_| panic!("no golden file for {}, run with UPDATE_GOLDEN=1", name));
+        let differences = diverged(&expected, &actual);
+        if !differences.is_empty() {
+            let shown = differences.iter().take(20).cloned().collect::<Vec<_>>().join("\n");
+            panic!("{} diverged in {} cells:\n{}", name, differences.len(), shown);
+        }
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        assert_eq!(run("doorway"), run("doorway"));
+    }
+
+    #[test]
+    fn doorway() {
+        check("doorway");
+    }
+
+    #[test]
+    fn layered_species() {
+        check("layered_species");
+    }
+
+    #[test]
+    fn vents_and_drains() {
+        check("vents_and_drains");
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod solid_grid {
+    use mint::{Point2};
+
+    use fluid_dynamics::{ConnectionGrid, Neighbourhood, SolidGrid};
+
+    #[test]
+    fn parse_map_characters() {
+        let g: SolidGrid = "
+            #####
+            #..0#
+            ##1.#
+        ".parse().unwrap();
+
+        assert_eq!(g.dimensions(), [5, 3].into());
+        assert!(g.is_solid([0, 0]));
+        assert!(!g.is_solid([1, 1]));
+        assert!(!g.is_solid([3, 1]));
+        assert!(g.is_solid([2, 2]));
+        assert!(g.is_solid([5, 1]));
+        assert_eq!(g.get_connections([3, 1]), vec![[2, 1].into(), [3, 2].into()]);
+    }
+
+    #[test]
+    fn parse_rejects_ragged_rows() {
+        assert_eq!("###\n##\n".parse::<SolidGrid>(), Err(()));
+        assert_eq!("#x#\n".parse::<SolidGrid>(), Err(()));
+    }
+
+    #[test]
+    fn from_fn_and_set_solid() {
+        let mut g = SolidGrid::from_fn(4, 4, |pos: Point2<usize>| pos.x == 0);
+
+        assert!(g.is_solid([0, 3]));
+        assert!(!g.is_solid([1, 3]));
+        assert_eq!(g.get_connections([1, 0]), vec![[2, 0].into(), [1, 1].into()]);
+
+        g.set_solid([2, 0], true);
+        assert_eq!(g.get_connections([1, 0]), vec![[1, 1].into()]);
+    }
+
+    #[test]
+    fn corners_with_moore_neighbourhood() {
+        let g = SolidGrid::new(3, 3).with_neighbourhood(Neighbourhood::Moore);
+
+        assert_eq!(g.get_connections([0, 0]).len(), 3);
+        assert_eq!(g.get_connections([1, 1]).len(), 8);
+        assert_eq!(g.neighbourhood(), &Neighbourhood::Moore);
+    }
+
+    #[test]
+    fn conductance_and_one_way() {
+        let mut g = SolidGrid::new(3, 1);
+
+        assert_eq!(g.conductance([0, 0], [1, 0]), 1f32);
+        g.set_conductance([0, 0], [1, 0], 0.25);
+        assert_eq!(g.conductance([1, 0], [0, 0]), 0.25);
+        g.set_conductance([0, 0], [1, 0], 7f32);
+        assert_eq!(g.conductance([0, 0], [1, 0]), 1f32);
+
+        g.set_one_way([1, 0], [2, 0]);
+        assert_eq!(g.get_connections([1, 0]), vec![[0, 0].into(), [2, 0].into()]);
+        assert_eq!(g.get_connections([2, 0]), vec![]);
+        assert_eq!(g.conductance([2, 0], [3, 0]), 0f32);
+    }
+}
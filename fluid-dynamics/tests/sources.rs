@@ -0,0 +1,117 @@
+mod common;
+
+#[cfg(test)]
+mod sources {
+    use fluid_dynamics::{FluidError, FluidGrid, SolidGrid, Source, SourceKind, Species};
+
+    use crate::common::{assert_fluid_eq};
+
+    fn closed_cell() -> SolidGrid {
+        "
+            111
+            101
+            111
+        ".parse().unwrap()
+    }
+
+    #[test]
+    fn constant_emitter_and_drain() {
+        let mut grid = FluidGrid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
+
+        grid.add_source(Source::emitter([0, 0], SourceKind::Constant { rate: 10f32 })).unwrap();
+        grid.add_source(Source::drain([4, 4], SourceKind::Constant { rate: 1f32 })).unwrap();
+
+        let report = grid.step(10, &g);
+        assert_fluid_eq(report.emitted, 100f32);
+        assert!(report.drained > 0f32);
+        assert!(report.is_conserved());
+        assert_fluid_eq(grid.total_fluid_level(), 100f32 - report.drained);
+    }
+
+    #[test]
+    fn drain_never_goes_negative() {
+        let mut grid = FluidGrid::new(3, 3);
+        grid.set_fluid([1, 1], 3f32).unwrap();
+        grid.add_source(Source::drain([1, 1], SourceKind::Constant { rate: 2f32 })).unwrap();
+
+        let report = grid.step(3, &closed_cell());
+        assert_fluid_eq(report.drained, 3f32);
+        assert_fluid_eq(*grid.get_fluid([1, 1]).unwrap(), 0f32);
+    }
+
+    #[test]
+    fn pulsed_emitter() {
+        let mut grid = FluidGrid::new(3, 3);
+        grid.add_source(Source::emitter([1, 1], SourceKind::Pulsed { amount: 5f32, interval: 3 })).unwrap();
+
+        let g = closed_cell();
+        grid.flow(&g);
+        assert_fluid_eq(grid.total_fluid_level(), 5f32);
+        grid.step(2, &g);
+        assert_fluid_eq(grid.total_fluid_level(), 5f32);
+        grid.flow(&g);
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+    }
+
+    #[test]
+    fn reservoir_runs_dry() {
+        let mut grid = FluidGrid::new(3, 3);
+        let handle = grid.add_source(Source::emitter([1, 1], SourceKind::Reservoir { rate: 4f32, remaining: 10f32 })).unwrap();
+
+        grid.step(5, &closed_cell());
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+        assert_eq!(grid.get_source(handle).unwrap().kind, SourceKind::Reservoir { rate: 4f32, remaining: 0f32 });
+    }
+
+    #[test]
+    fn pressure_limited_emitter_stops_at_limit() {
+        let mut grid = FluidGrid::new(3, 3);
+        grid.add_source(Source::emitter([1, 1], SourceKind::PressureLimited { rate: 4f32, limit: 10f32 })).unwrap();
+
+        grid.step(5, &closed_cell());
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+    }
+
+    #[test]
+    fn pause_and_remove_sources() {
+        let mut grid = FluidGrid::new(3, 3);
+        let water = grid.add_species(Species::new("water", 1f32, 1f32)).unwrap();
+        let handle = grid.add_source(Source::emitter([1, 1], SourceKind::Constant { rate: 1f32 }).with_species(water)).unwrap();
+        let g = closed_cell();
+
+        assert!(grid.pause_source(handle));
+        grid.step(2, &g);
+        assert_fluid_eq(grid.total_species_level(water), 0f32);
+
+        assert!(grid.resume_source(handle));
+        grid.step(2, &g);
+        assert_fluid_eq(grid.total_species_level(water), 2f32);
+        assert_eq!(grid.sources().count(), 1);
+
+        let removed = grid.remove_source(handle).unwrap();
+        assert_eq!(removed.species, water);
+        assert_eq!(grid.sources().count(), 0);
+        assert!(!grid.pause_source(handle));
+        grid.step(2, &g);
+        assert_fluid_eq(grid.total_species_level(water), 2f32);
+    }
+
+    #[test]
+    fn sources_stay_out_of_walls() {
+        let mut grid = FluidGrid::new(3, 3);
+        let mut g = SolidGrid::new(3, 3);
+        g.set_solid([0, 0], true);
+        grid.flow(&g);
+
+        let wall = Source::emitter([0, 0], SourceKind::Constant { rate: 1f32 });
+        assert_eq!(grid.add_source(wall), Err(FluidError::CellSolid([0, 0].into())));
+
+        grid.add_source(Source::emitter([2, 2], SourceKind::Constant { rate: 1f32 })).unwrap();
+        g.set_solid([2, 2], true);
+        grid.invalidate_cell([2, 2]);
+        grid.step(2, &g);
+        assert_fluid_eq(grid.total_fluid_level(), 0f32);
+        assert_eq!(grid.get_fluid([2, 2]), Some(&0f32));
+    }
+}
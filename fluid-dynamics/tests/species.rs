@@ -0,0 +1,69 @@
+mod common;
+
+#[cfg(test)]
+mod species {
+    use fluid_dynamics::{FluidGrid, SolidGrid, Species, SpeciesId};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn species_flow_independently() {
+        let mut grid = FluidGrid::with_species(5, 5, vec![
+            Species::new("water", 1f32, 1f32),
+            Species::new("acid", 0.5f32, 1.5f32),
+        ]);
+        let water = grid.species_id("water").unwrap();
+        let acid = grid.species_id("acid").unwrap();
+
+        assert_eq!(grid.set_species_fluid(water, [0, 0], 10f32), Ok(10f32));
+        assert_eq!(grid.set_species_fluid(acid, [0, 0], 10f32), Ok(10f32));
+        assert_fluid_eq(grid.total_fluid_level(), 20f32);
+
+        let g = SolidGrid::new(5, 5);
+
+        grid.flow(&g);
+
+        assert_eq!(grid.get_species_fluid(water, [1, 0]), Some(&2f32));
+        assert_eq!(grid.get_species_fluid(water, [0, 0]), Some(&6f32));
+        assert_eq!(grid.get_species_fluid(acid, [1, 0]), Some(&1f32));
+        assert_eq!(grid.get_species_fluid(acid, [0, 0]), Some(&8f32));
+        assert_fluid_eq(grid.total_species_level(water), 10f32);
+        assert_fluid_eq(grid.total_species_level(acid), 10f32);
+        assert_eq!(grid.get_fluid([1, 0]), grid.get_species_fluid(water, [1, 0]));
+    }
+
+    #[test]
+    fn dense_species_displace_light() {
+        let mut grid = FluidGrid::new(5, 5);
+        let smoke = grid.add_species(Species::new("smoke", 0f32, 0.1f32)).unwrap();
+        let water = grid.add_species(Species::new("water", 0f32, 1f32)).unwrap();
+        grid.set_capacity(10f32).unwrap();
+
+        grid.set_species_fluid(smoke, [2, 2], 8f32).unwrap();
+        grid.set_species_fluid(water, [2, 2], 6f32).unwrap();
+
+        let g = SolidGrid::new(5, 5);
+
+        grid.flow(&g);
+
+        assert_fluid_eq(*grid.get_species_fluid(water, [2, 2]).unwrap(), 6f32);
+        assert_fluid_eq(*grid.get_species_fluid(smoke, [2, 2]).unwrap(), 4f32);
+        assert_fluid_eq(*grid.get_species_fluid(smoke, [2, 1]).unwrap(), 1f32);
+        assert_fluid_eq(grid.total_species_level(smoke), 8f32);
+        assert_fluid_eq(grid.total_fluid_level(), 14f32);
+    }
+
+    #[test]
+    fn viscocity_of_all_or_one_species() {
+        let mut grid = FluidGrid::new(2, 1);
+        let gas = grid.add_species(Species::new("gas", 0f32, 0.1)).unwrap();
+
+        grid.set_viscocity(0.5).unwrap();
+        assert!(grid.species().all(|(_, species)| species.viscocity == 0.5));
+
+        grid.set_species_viscocity(gas, 0.25).unwrap();
+        assert_eq!(grid.get_species(gas).unwrap().viscocity, 0.25);
+        assert_eq!(grid.get_species(SpeciesId::default()).unwrap().viscocity, 0.5);
+        assert!(grid.set_species_viscocity(gas, -1f32).is_err());
+    }
+}
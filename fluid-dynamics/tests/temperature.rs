@@ -0,0 +1,100 @@
+mod common;
+
+#[cfg(test)]
+mod temperature {
+    use fluid_dynamics::{FluidError, FluidGrid, HeatSource, SolidGrid, Temperature};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn disabled_by_default() {
+        let mut grid = FluidGrid::new(3, 3);
+
+        assert_eq!(grid.temperature(), None);
+        assert_eq!(grid.get_temperature([1, 1]), None);
+        assert_eq!(grid.set_temperature([1, 1], 50f32), Err(FluidError::NoTemperature));
+        assert_eq!(grid.add_heat_source(HeatSource::new([1, 1], 50f32, 1f32)).err(), Some(FluidError::NoTemperature));
+
+        grid.enable_temperature(Temperature::new(15f32)).unwrap();
+        assert_eq!(grid.get_temperature([1, 1]), Some(15f32));
+        assert!(grid.set_temperature([1, 1], f32::NAN).is_err());
+    }
+
+    #[test]
+    fn heat_is_conducted_and_cools_off() {
+        let mut grid = FluidGrid::new(5, 1);
+        let g = SolidGrid::new(5, 1);
+        grid.enable_temperature(Temperature::default()).unwrap();
+        let fire = grid.add_heat_source(HeatSource::new([0, 0], 500f32, 1f32)).unwrap();
+
+        grid.step(10, &g);
+        let near = grid.get_temperature([1, 0]).unwrap();
+        let far = grid.get_temperature([4, 0]).unwrap();
+        assert!(grid.get_temperature([0, 0]).unwrap() > near);
+        assert!(near > far && far > 20f32);
+
+        assert!(grid.pause_source(fire));
+        grid.step(300, &g);
+        for x in 0..5 {
+            assert_fluid_eq(grid.get_temperature([x, 0]).unwrap(), 20f32);
+        }
+    }
+
+    #[test]
+    fn fluid_pools_where_it_is_cold() {
+        let mut grid = FluidGrid::new(5, 1);
+        let g = SolidGrid::new(5, 1);
+        grid.enable_temperature(Temperature { cooling: 0f32, conductivity: 0f32, ..Temperature::default() }).unwrap();
+        grid.add_heat_source(HeatSource::new([0, 0], 200f32, 1f32)).unwrap();
+        grid.add_heat_source(HeatSource::new([4, 0], -100f32, 1f32)).unwrap();
+        for x in 0..5 {
+            grid.set_fluid([x, 0], 10f32).unwrap();
+        }
+
+        grid.step(100, &g);
+        assert_fluid_eq(grid.total_fluid_level(), 50f32);
+        assert!(*grid.get_fluid([4, 0]).unwrap() > 10f32);
+        assert!(*grid.get_fluid([0, 0]).unwrap() < 10f32);
+    }
+
+    #[test]
+    fn resize_keeps_temperature() {
+        let mut grid = FluidGrid::new(3, 3);
+        grid.enable_temperature(Temperature::new(10f32)).unwrap();
+        grid.set_temperature([2, 2], 90f32).unwrap();
+
+        grid.resize(4, 4);
+        assert_eq!(grid.get_temperature([2, 2]), Some(90f32));
+        assert_eq!(grid.get_temperature([3, 3]), Some(10f32));
+        assert_eq!(grid.sub_grid(fluid_dynamics::Rect::new(2, 2, 2, 2)).get_temperature([0, 0]), Some(90f32));
+    }
+
+    #[test]
+    fn conduction_goes_both_ways() {
+        let mut grid = FluidGrid::new(2, 1);
+        let mut g = SolidGrid::new(2, 1);
+        g.set_one_way([0, 0], [1, 0]);
+        grid.enable_temperature(Temperature { cooling: 0f32, ..Temperature::new(20f32) }).unwrap();
+        grid.set_temperature([1, 0], 100f32).unwrap();
+
+        grid.step(500, &g);
+        assert_fluid_eq(grid.get_temperature([0, 0]).unwrap(), 60f32);
+        assert_fluid_eq(grid.get_temperature([1, 0]).unwrap(), 60f32);
+    }
+
+    #[test]
+    fn heat_wakes_sleeping_chunks() {
+        let mut grid = FluidGrid::new(24, 1);
+        let g = SolidGrid::new(24, 1);
+        grid.set_chunk_size(4).unwrap();
+        grid.enable_temperature(Temperature { cooling: 0f32, ..Temperature::default() }).unwrap();
+        grid.step(2, &g);
+        assert!(grid.is_stable());
+
+        grid.set_temperature([0, 0], 1000f32).unwrap();
+        grid.step(100, &g);
+        assert!(grid.get_temperature([12, 0]).unwrap() > 20f32);
+        let total = (0..24).map(|x| grid.get_temperature([x, 0]).unwrap()).sum::<f32>();
+        assert!((total - (1000f32 + 23f32 * 20f32)).abs() < 0.1);
+    }
+}
@@ -0,0 +1,96 @@
+mod common;
+
+#[cfg(test)]
+mod timestep {
+    use fluid_dynamics::{FluidError, FluidGrid, SolidGrid};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn stepping_matches_repeated_flow() {
+        let g: SolidGrid = "
+            00000
+            11011
+            01010
+            00000
+            00000
+        ".parse().unwrap();
+        let mut flowed = FluidGrid::new(5, 5);
+        let mut stepped = FluidGrid::new(5, 5);
+        let mut timed = FluidGrid::new(5, 5);
+        timed.set_timestep(0.5).unwrap();
+        timed.set_viscocity(2f32).unwrap();
+        for grid in [&mut flowed, &mut stepped, &mut timed].iter_mut() {
+            grid.set_fluid([2, 0], 60f32).unwrap();
+        }
+
+        for _ in 0..6 {
+            flowed.flow(&g);
+        }
+        let report = stepped.step(6, &g);
+        for _ in 0..30 {
+            timed.flow_for(0.1, &g);
+        }
+
+        assert_eq!(report.steps, 6);
+        assert_fluid_eq(report.before, 60f32);
+        assert_fluid_eq(report.after, 60f32);
+        assert_eq!(flowed.iter().collect::<Vec<_>>(), stepped.iter().collect::<Vec<_>>());
+        for ((_, a), (_, b)) in flowed.iter().zip(timed.iter()) {
+            assert_fluid_eq(a, b);
+        }
+    }
+
+    #[test]
+    fn flow_for_carries_remainder() {
+        let mut grid = FluidGrid::new(5, 5);
+        grid.set_timestep(0.5).unwrap();
+        grid.set_fluid([0, 0], 10f32).unwrap();
+
+        let g = SolidGrid::new(5, 5);
+
+        assert_eq!(grid.flow_for(0.3, &g).steps, 0);
+        assert_fluid_eq(grid.interpolation(), 0.6);
+        assert_eq!(grid.flow_for(0.3, &g).steps, 1);
+        assert_fluid_eq(grid.interpolation(), 0.2);
+        assert_eq!(grid.flow_for(1.2, &g).steps, 2);
+
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+    }
+
+    #[test]
+    fn substeps_split_a_step() {
+        let mut grid = FluidGrid::new(5, 5);
+        grid.set_substeps(2);
+
+        assert_eq!(grid.set_fluid([2, 2], 10f32), Ok(10f32));
+
+        let g = SolidGrid::new(5, 5);
+
+        grid.flow(&g);
+
+        assert_fluid_eq(*grid.get_fluid([2, 2]).unwrap(), 10f32 * 0.6 * 0.6 + 4f32 * 0.1 * 0.8125);
+        assert_fluid_eq(grid.total_fluid_level(), 10f32);
+    }
+
+    #[test]
+    fn bad_times_are_rejected() {
+        let mut grid = FluidGrid::new(3, 1);
+        grid.set_fluid([0, 0], 3f32).unwrap();
+        let g = SolidGrid::new(3, 1);
+
+        assert_eq!(grid.set_timestep(0f32), Err(FluidError::NotPositive(0f32)));
+        assert_eq!(grid.set_timestep(-1f32), Err(FluidError::NegativeAmount(-1f32)));
+        assert!(grid.set_timestep(f32::NAN).is_err());
+        assert!(grid.set_timestep(f32::INFINITY).is_err());
+        assert_eq!(grid.timestep(), 1f32);
+
+        grid.flow_for(0.5, &g);
+        for dt in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, -1f32].iter() {
+            assert_eq!(grid.flow_for(*dt, &g).steps, 0);
+            assert_fluid_eq(grid.interpolation(), 0.5);
+        }
+        assert_eq!(grid.flow_for(0.5, &g).steps, 1);
+        assert_fluid_eq(grid.total_fluid_level(), 3f32);
+    }
+}
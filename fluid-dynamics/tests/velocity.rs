@@ -0,0 +1,38 @@
+mod common;
+
+#[cfg(test)]
+mod velocity {
+    use fluid_dynamics::{FluidGrid, SolidGrid};
+
+    use crate::common::{assert_fluid_eq};
+
+    #[test]
+    fn velocity_builds_up_in_open_room() {
+        let mut grid = FluidGrid::new(5, 5);
+        let g = SolidGrid::new(5, 5);
+
+        assert_eq!(grid.set_fluid([2, 2], 50f32), Ok(50f32));
+        grid.flow(&g);
+
+        let up = *grid.get_velocity([2, 1]).unwrap();
+        assert_fluid_eq(up.x, 0f32);
+        assert!(up.y < 0f32);
+        let still = *grid.get_velocity([2, 2]).unwrap();
+        assert_fluid_eq(still.x, 0f32);
+        assert_fluid_eq(still.y, 0f32);
+
+        grid.flow(&g);
+
+        let diagonal = *grid.get_velocity([3, 1]).unwrap();
+        assert!(diagonal.x > 0f32 && diagonal.y < 0f32);
+        let further = *grid.get_velocity([2, 0]).unwrap();
+        assert!(further.y < up.y);
+    }
+
+    #[test]
+    fn velocity_as_text() {
+        let grid = FluidGrid::new(2, 2);
+
+        assert_eq!(grid.velocity_text(), "  0.00, 0.00   0.00, 0.00 \n  0.00, 0.00   0.00, 0.00 \n");
+    }
+}
@@ -10,9 +10,11 @@ use quicksilver::{
     Future, Result,
 };
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use tracing::{debug};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Color {
     r: f32,
     g: f32,
@@ -78,8 +80,7 @@ impl Console {
                 .render(&game_glyphs, &FontStyle::new(12f32, QuickColor::WHITE))
                 .expect("Could not render the font tileset.");
             let mut tileset = HashMap::new();
-            println!("tile size: {:?}", tile_size);
-            println!("tiles: {:?}", tiles.area());
+            debug!(?tile_size, tiles = ?tiles.area(), "rendered tileset");
             for (index, glyph) in game_glyphs.chars().enumerate() {
                 let pos = (index as i32 * tile_size.x as i32, 0);
                 let tile = tiles.subimage(Rectangle::new(pos, tile_size));
@@ -1,24 +1,27 @@
 use mint::{Point2};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::console::{Color};
 
 pub type EntityID = u64;
 
+#[derive(Serialize, Deserialize)]
 pub struct EntityManager {
     next_id: EntityID,
     pub visual: HashMap<EntityID, Visual>,
     pub physics: HashMap<EntityID, Physics>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Physics {
     pub position: Point2<usize>,
     pub durability: u32,
     pub hardness: u32,
+    pub anchored: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Visual {
     pub glyph: char,
     pub foreground: Color,
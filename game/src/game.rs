@@ -1,29 +1,124 @@
-use mint::Point2;
-use fluid_dynamics::FluidGrid;
+use mint::{Point2, Vector2};
+use std::collections::HashMap;
+use tracing::{info};
+use fluid_dynamics::{FluidGrid, Reaction, ReactionEvent, ReactionTable, Species, Temperature};
 
-use fluid_dynamics::ConnectionGrid;
+use fluid_dynamics::{ConnectionGrid, SolidGrid};
 use crate::map::{Tile, Map};
 use crate::console::{Console, Cell};
 use crate::entities::{EntityID, EntityManager, Visual, Physics};
 
+/*
+ * Simulated time for the miasma every time the player takes an action
+ */
+const TURN_DURATION: f32 = 1f32;
+
+/*
+ * Miasma eats away at anything solid it touches and goes up in flames when it
+ * gets hot enough
+ */
+fn reactions(miasma: &FluidGrid) -> ReactionTable {
+    let mut table = ReactionTable::new();
+    if let Some(species) = miasma.species_id("miasma") {
+        let corrosion = Reaction::new(vec![(species, 1f32)], vec![], 0.02)
+            .on_surface()
+            .with_damage(5f32);
+        let explosion = Reaction::new(vec![(species, 1f32)], vec![], 0.5)
+            .above(150f32)
+            .with_heat(10f32)
+            .with_pressure(2f32);
+        table.add(corrosion).unwrap();
+        table.add(explosion).unwrap();
+    }
+    table
+}
+
 pub struct World {
     pub map: Map,
     pub entity_manager: EntityManager,
+    solids: SolidGrid,
+    changed: Vec<Point2<usize>>,
 }
 
 impl World {
     fn new(map: Map) -> Self {
+        World::with_entities(map, EntityManager::default())
+    }
+
+    fn with_entities(map: Map, entity_manager: EntityManager) -> Self {
+        let solids = SolidGrid::from_fn(map.width, map.height, |pos| {
+            map.tiles.get(map.index(pos)) != Some(&Tile::Floor)
+                || entity_manager.physics.values().any(|physics| physics.position == pos)
+        });
         World {
             map,
-            entity_manager: EntityManager::default(),
+            entity_manager,
+            solids,
+            changed: vec![],
         }
     }
+
+    /*
+     * Every change to the solid cells is remembered, so the miasma can be told
+     * which of its cached connections are out of date
+     */
+    fn set_solid(&mut self, pos: Point2<usize>, solid: bool) {
+        self.solids.set_solid(pos, solid);
+        self.changed.push(pos);
+    }
+
+    pub fn take_changes(&mut self) -> Vec<Point2<usize>> {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn add_physics(&mut self, entity_id: EntityID, physics: Physics) {
+        self.set_solid(physics.position, true);
+        self.entity_manager.add_physics(entity_id, physics);
+    }
+
+    /*
+     * Moves an entity one cell along the strongest axis of the force, if there
+     * is room for it there
+     */
+    pub fn push_entity(&mut self, entity_id: &EntityID, force: Vector2<f32>) {
+        let from = match self.entity_manager.get_physics(entity_id) {
+            Some(physics) => physics.position,
+            None => return
+        };
+        let (dx, dy) = if force.x.abs() >= force.y.abs() {
+            (force.x.signum() as isize, 0)
+        } else {
+            (0, force.y.signum() as isize)
+        };
+        let (x, y) = (from.x as isize + dx, from.y as isize + dy);
+        if x < 0 || y < 0 || self.is_solid([x as usize, y as usize]) {
+            return;
+        }
+        let to: Point2<usize> = [x as usize, y as usize].into();
+        let wall = self.map.tiles.get(self.map.index(from)) != Some(&Tile::Floor);
+        self.set_solid(from, wall);
+        self.set_solid(to, true);
+        if let Some(physics) = self.entity_manager.physics.get_mut(entity_id) {
+            physics.position = to;
+        }
+    }
+
+    pub fn delete_entity(&mut self, entity_id: &EntityID) {
+        if let Some(physics) = self.entity_manager.get_physics(entity_id) {
+            let pos = physics.position;
+            let wall = self.map.tiles.get(self.map.index(pos)) != Some(&Tile::Floor);
+            self.set_solid(pos, wall);
+        }
+        self.entity_manager.delete_entity(entity_id);
+    }
 }
 
 pub struct GameState {
     pub pos: Point2<usize>,
     pub miasma: FluidGrid,
-    pub world: World
+    pub world: World,
+    reactions: ReactionTable,
+    wear: HashMap<EntityID, f32>,
 }
 
 impl GameState {
@@ -39,45 +134,121 @@ impl GameState {
 
     pub fn from_str(input: &str) -> Self {
         let map: Map = input.parse().unwrap();
+        let mut miasma = FluidGrid::with_species(map.width, map.height, vec![Species::new("miasma", 1f32, 1f32)]);
+        miasma.enable_temperature(Temperature::default()).unwrap();
         GameState {
             pos: [5, 5].into(),
-            miasma: FluidGrid::new(map.width, map.height),
+            reactions: reactions(&miasma),
+            miasma,
             world: World::new(map),
+            wear: HashMap::new(),
         }
     }
 
+    /*
+     * Puts a saved session back together, the solid cells follow from the map
+     * and the entities
+     */
+    pub fn restore(pos: Point2<usize>, map: Map, entity_manager: EntityManager, miasma: FluidGrid, wear: HashMap<EntityID, f32>) -> Self {
+        GameState {
+            pos,
+            reactions: reactions(&miasma),
+            miasma,
+            world: World::with_entities(map, entity_manager),
+            wear,
+        }
+    }
+
+    /*
+     * Corrosion that has not added up to a whole point of durability yet
+     */
+    pub fn wear(&self) -> &HashMap<EntityID, f32> {
+        &self.wear
+    }
+
     pub fn draw(&self, console: &mut Console) {
         self.blit_map(console);
         self.blit_miasma(console);
         self.blit_entities(console);
     }
 
-    pub fn flow(&mut self) {
-        self.miasma.flow(&self.world);
+    pub fn flow(&mut self) -> Vec<ReactionEvent> {
+        for pos in self.world.take_changes() {
+            self.miasma.invalidate_cell(pos);
+        }
+        self.miasma.flow_for(TURN_DURATION, &self.world);
+        self.reactions.react(&mut self.miasma, &self.world, TURN_DURATION)
     }
 
+    /*
+     * Corrosion wears down the entities it touches a little at a time, while
+     * explosions hit everything around them at once
+     */
+    pub fn handle_reactions(&mut self, events: Vec<ReactionEvent>) {
+        let (world, wear) = (&mut self.world, &mut self.wear);
+        let mut to_delete = vec![];
+        for event in events {
+            world.entity_manager.physics.iter_mut().for_each(|(id, physics)| {
+                let pos = physics.position;
+                if physics.durability == 0 {
+                    return;
+                }
+                let damage = if event.surface == Some(pos) {
+                    let worn = wear.entry(*id).or_insert(0f32);
+                    *worn += event.damage;
+                    let whole = worn.floor();
+                    *worn -= whole;
+                    whole as u32
+                } else if pos.x.abs_diff(event.position.x) <= 1 && pos.y.abs_diff(event.position.y) <= 1 {
+                    let pressure = event.pressure.floor() as u32;
+                    if pressure >= physics.hardness { pressure } else { 0 }
+                } else {
+                    0
+                };
+                if damage > 0 {
+                    physics.durability = physics.durability.saturating_sub(damage);
+                    if physics.durability == 0 {
+                        info!(entity = *id, reaction = event.reaction.index(), "entity destroyed by reaction");
+                        to_delete.push(*id);
+                    }
+                }
+            });
+        }
+        to_delete.iter().for_each(|id| {
+            wear.remove(id);
+            world.delete_entity(id);
+        });
+    }
+
+    /*
+     * Entities are damaged by the difference in pressure between their sides,
+     * and the ones that are not anchored get pushed along by it
+     */
     pub fn handle_pressure(&mut self) {
         let (miasma, world) = (&self.miasma, &mut self.world);
         let mut to_delete = vec![];
+        let mut to_push = vec![];
         world.entity_manager.physics.iter_mut().for_each(|(id, physics)| {
             if physics.durability > 0 {
-                if let Some(pressure) = miasma.get_pressure(physics.position) {
-                    let pressure = pressure.floor() as u32;
+                if let Some(force) = miasma.get_force(physics.position) {
+                    let pressure = (force.x * force.x + force.y * force.y).sqrt().floor() as u32;
                     if pressure >= physics.hardness {
-                        if pressure <= physics.durability {
-                            physics.durability = physics.durability - pressure;
-                        } else {
-                            physics.durability = 0;
-                        }
+                        physics.durability = physics.durability.saturating_sub(pressure);
                         if physics.durability == 0 {
+                            info!(entity = *id, force.x, force.y, "entity blown open");
                             to_delete.push(*id);
+                        } else if !physics.anchored {
+                            to_push.push((*id, *force));
                         }
                     }
                 }
             }
         });
         to_delete.iter().for_each(|id| {
-            world.entity_manager.delete_entity(id);
+            world.delete_entity(id);
+        });
+        to_push.into_iter().for_each(|(id, force)| {
+            world.push_entity(&id, force);
         });
     }
 
@@ -98,10 +269,11 @@ impl GameState {
                 glyph: '=',
                 foreground: (1f32, 0f32, 1f32, 1f32).into()
             });
-            self.world.entity_manager.add_physics(entity, Physics {
+            self.world.add_physics(entity, Physics {
                 position: pos,
                 hardness: 5,
-                durability: 100
+                durability: 100,
+                anchored: true
             });
         }
     }
@@ -150,48 +322,14 @@ impl GameState {
 
 impl ConnectionGrid for World {
     fn get_connections<T: Into<Point2<usize>>>(&self, pos: T) -> Vec<Point2<usize>> {
-        let point = pos.into();
-        let mut connections = vec![];
-        if point.x != 0 {
-            let nx = (point.x - 1) as usize;
-            let ny = point.y as usize;
-            if !self.is_solid([nx, ny]) {
-                connections.push([point.x - 1, point.y].into());
-            }
-        }
-        if point.y != 0 {
-            let nx = point.x as usize;
-            let ny = (point.y - 1) as usize;
-            if !self.is_solid([nx, ny]) {
-                connections.push([point.x, point.y - 1].into());
-            }
-        }
-        if point.x < self.map.width - 1 {
-            let nx = (point.x + 1) as usize;
-            let ny = point.y as usize;
-            if !self.is_solid([nx, ny]) {
-                connections.push([point.x + 1, point.y].into());
-            }
-        }
-        if point.y < self.map.height - 1 {
-            let nx = point.x as usize;
-            let ny = (point.y + 1) as usize;
-            if !self.is_solid([nx, ny]) {
-                connections.push([point.x, point.y + 1].into());
-            }
-        }
-        connections
+        self.solids.get_connections(pos)
     }
 
     fn is_solid<T: Into<Point2<usize>>>(&self, pos: T) -> bool {
-        let pos = pos.into();
-        if pos.x >= self.map.width || pos.y >= self.map.height {
-            return true;
-        }
-        let idx = self.map.index(pos);
-        let has_entity = self.entity_manager.physics.iter().any(|(_, physics)| {
-            physics.position == pos
-        });
-        has_entity || self.map.tiles.get(idx) == Some(&Tile::Wall) || self.map.tiles.get(idx) == None
+        self.solids.is_solid(pos)
+    }
+
+    fn conductance<T: Into<Point2<usize>>>(&self, from: T, to: T) -> f32 {
+        self.solids.conductance(from, to)
     }
 }
@@ -11,13 +11,21 @@ mod console;
 mod game;
 mod map;
 mod entities;
+mod save;
+
+use fluid_dynamics::{HeatSource, Source, SourceKind};
+use tracing::{debug, debug_span, info, warn};
+use tracing_subscriber::{EnvFilter};
+use tracing_subscriber::fmt::format::{FmtSpan};
 
 use console::{Console, Cell};
 use game::GameState;
 
 struct GameScreen {
     console: Console,
-    state: GameState
+    state: GameState,
+    slot: u32,
+    turn: u64
 }
 
 impl GameScreen {
@@ -31,7 +39,9 @@ impl State for GameScreen {
         let state = GameState::from_str(&map_contents);
         Ok(GameScreen {
             console: Console::new(state.world.map.width, state.world.map.height, "square.ttf".to_string()),
-            state
+            state,
+            slot: 1,
+            turn: 0
         })
     }
 
@@ -60,30 +70,91 @@ impl State for GameScreen {
             action = true;
         }
         if window.keyboard()[Key::W] == Pressed {
-            println!("Inserting fluid");
-            self.state.miasma.add_fluid(self.state.pos, 100f32);
-            let fluid = self.state.miasma.total_fluid_level();
-            println!("Fluid level: {}", fluid);
+            match self.state.miasma.add_fluid(self.state.pos, 100f32) {
+                Ok(_) => info!(level = self.state.miasma.total_fluid_level(), "inserted fluid"),
+                Err(e) => warn!("Could not insert fluid: {}", e),
+            }
+        }
+        if window.keyboard()[Key::E] == Pressed {
+            match self.state.miasma.add_source(Source::emitter(self.state.pos, SourceKind::Constant { rate: 10f32 })) {
+                Ok(handle) => info!(?handle, "added miasma source"),
+                Err(e) => warn!("Could not add source: {}", e),
+            }
+        }
+        if window.keyboard()[Key::S] == Pressed {
+            match self.state.miasma.add_source(Source::drain(self.state.pos, SourceKind::Constant { rate: 10f32 })) {
+                Ok(handle) => info!(?handle, "added miasma drain"),
+                Err(e) => warn!("Could not add drain: {}", e),
+            }
+        }
+        if window.keyboard()[Key::F] == Pressed {
+            match self.state.miasma.add_heat_source(HeatSource::new(self.state.pos, 400f32, 0.5)) {
+                Ok(handle) => info!(?handle, "lit a fire"),
+                Err(e) => warn!("Could not light a fire: {}", e),
+            }
+        }
+        if window.keyboard()[Key::R] == Pressed {
+            let pos = self.state.pos;
+            let handles = self.state.miasma.sources()
+                .filter(|(_, source)| source.position == pos)
+                .map(|(handle, _)| handle)
+                .collect::<Vec<_>>();
+            handles.into_iter().for_each(|handle| {
+                self.state.miasma.remove_source(handle);
+            });
+            let fires = self.state.miasma.heat_sources()
+                .filter(|(_, source)| source.position == pos)
+                .map(|(handle, _)| handle)
+                .collect::<Vec<_>>();
+            fires.into_iter().for_each(|handle| {
+                self.state.miasma.remove_heat_source(handle);
+            });
         }
         if window.keyboard()[Key::V] == Pressed {
             let fluid = self.state.miasma.total_fluid_level();
-            println!("Fluid level: {}", fluid);
+            info!("Fluid level: {}", fluid);
         }
         if window.keyboard()[Key::D] == Pressed {
             self.state.add_door(self.state.pos);
         }
         if window.keyboard()[Key::P] == Pressed {
              let pressure = self.state.miasma.get_pressure(self.state.pos);
-             println!("Pressure: {:?}", pressure);
+             info!("Pressure: {:?}", pressure);
         }
         if window.keyboard()[Key::X] == Pressed {
              if let Some((id, _)) = self.state.world.entity_manager.physics.iter().find(|(_, physics)| {
                  physics.position == self.state.pos
              }) {
-                println!("physics: {:?}", self.state.world.entity_manager.get_physics(id));
+                info!("physics: {:?}", self.state.world.entity_manager.get_physics(id));
              }
         }
 
+        for (key, slot) in [(Key::F1, 1), (Key::F2, 2), (Key::F3, 3)].iter() {
+            if window.keyboard()[*key] == Pressed {
+                self.slot = *slot;
+                info!("Using save slot {}", slot);
+            }
+        }
+        if window.keyboard()[Key::F5] == Pressed {
+            match save::save(&self.state, self.slot) {
+                Ok(()) => info!("Saved to slot {}", self.slot),
+                Err(e) => warn!("Could not save to slot {}: {}", self.slot, e),
+            }
+        }
+        if window.keyboard()[Key::F9] == Pressed {
+            match save::load(self.slot) {
+                Ok(state) => {
+                    let map = &state.world.map;
+                    if map.width != self.state.world.map.width || map.height != self.state.world.map.height {
+                        self.console = Console::new(map.width, map.height, "square.ttf".to_string());
+                    }
+                    self.state = state;
+                    info!("Loaded slot {}", self.slot);
+                },
+                Err(e) => warn!("Could not load slot {}: {}", self.slot, e),
+            }
+        }
+
         if window.keyboard()[Key::Q].is_down() {
             window.close();
         }
@@ -92,8 +163,12 @@ impl State for GameScreen {
         }
 
         if action {
-            self.state.flow();
+            self.turn += 1;
+            let _span = debug_span!("turn", turn = self.turn, x = self.state.pos.x, y = self.state.pos.y).entered();
+            let events = self.state.flow();
             self.state.handle_pressure();
+            self.state.handle_reactions(events);
+            debug!(level = self.state.miasma.total_fluid_level(), "turn over");
         }
         Ok(())
     }
@@ -108,7 +183,21 @@ impl State for GameScreen {
     }
 }
 
+/*
+ * Logging is set up from MIASMA_LOG using the usual filter syntax, for example
+ * MIASMA_LOG=fluid_dynamics=debug to follow every flow step. The time spent in
+ * each turn and flow step is logged when its span closes
+ */
+fn init_logging() {
+    let filter = EnvFilter::try_from_env("MIASMA_LOG").unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+}
+
 pub fn main() -> Result<()> {
+    init_logging();
     std::env::set_var("WINIT_HIDPI_FACTOR", "1.0");
 
     let settings = Settings {
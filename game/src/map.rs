@@ -1,7 +1,8 @@
 use mint::Point2;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Tile {
     Floor,
     Wall
@@ -16,6 +17,7 @@ impl From<Tile> for char {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Map {
     pub tiles: Vec<Tile>,
     pub width: usize,
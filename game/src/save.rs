@@ -0,0 +1,132 @@
+use mint::{Point2};
+use quicksilver::saving;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use fluid_dynamics::{FluidGrid, FluidSave};
+use crate::entities::{EntityID, EntityManager};
+use crate::game::{GameState};
+use crate::map::{Map};
+
+const APP_NAME: &str = "miasma";
+const SAVE_VERSION: u32 = 1;
+
+/*
+ * What is written to a save slot. The session is kept as text next to its
+ * checksum so a damaged save is noticed before anything is restored. The game
+ * has no random number generator yet, so there is no RNG state to keep
+ */
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    checksum: u64,
+    session: String,
+}
+
+#[derive(Serialize)]
+struct SessionRef<'a> {
+    pos: Point2<usize>,
+    map: &'a Map,
+    entity_manager: &'a EntityManager,
+    miasma: &'a FluidGrid,
+    wear: &'a HashMap<EntityID, f32>,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    pos: Point2<usize>,
+    map: Map,
+    entity_manager: EntityManager,
+    miasma: FluidSave,
+    #[serde(default)]
+    wear: HashMap<EntityID, f32>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Storage(String),
+    Encode(String),
+    Incompatible(u32),
+    Corrupt(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Storage(error) => write!(f, "could not access save: {}", error),
+            SaveError::Encode(error) => write!(f, "could not write the session: {}", error),
+            SaveError::Incompatible(version) => write!(f, "save version {} is not supported", version),
+            SaveError::Corrupt(reason) => write!(f, "save is corrupt: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+fn profile(slot: u32) -> String {
+    format!("slot{}", slot)
+}
+
+/*
+ * 64 bit FNV-1a, enough to notice a save that was truncated or edited
+ */
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub fn save(state: &GameState, slot: u32) -> Result<(), SaveError> {
+    let session = SessionRef {
+        pos: state.pos,
+        map: &state.world.map,
+        entity_manager: &state.world.entity_manager,
+        miasma: &state.miasma,
+        wear: state.wear(),
+    };
+    let session = serde_json::to_string(&session).map_err(|e| SaveError::Encode(e.to_string()))?;
+    let file = SaveFile {
+        version: SAVE_VERSION,
+        checksum: checksum(session.as_bytes()),
+        session,
+    };
+    saving::save(APP_NAME, &profile(slot), &file).map_err(|e| SaveError::Storage(e.to_string()))
+}
+
+pub fn load(slot: u32) -> Result<GameState, SaveError> {
+    let file: SaveFile = saving::load(APP_NAME, &profile(slot)).map_err(|e| SaveError::Storage(e.to_string()))?;
+    if file.version != SAVE_VERSION {
+        return Err(SaveError::Incompatible(file.version));
+    }
+    if checksum(file.session.as_bytes()) != file.checksum {
+        return Err(SaveError::Corrupt("checksum does not match".to_string()));
+    }
+    let session: Session = serde_json::from_str(&file.session).map_err(|e| SaveError::Corrupt(e.to_string()))?;
+    /*
+     * The miasma checks its own fields, so a damaged grid is reported as
+     * corrupt instead of being restored
+     */
+    let miasma = FluidGrid::from_save(session.miasma).map_err(|e| match e {
+        fluid_dynamics::SaveError::UnsupportedVersion(version) => SaveError::Incompatible(version),
+        e => SaveError::Corrupt(e.to_string()),
+    })?;
+
+    let map = &session.map;
+    if map.tiles.len() != map.width * map.height {
+        return Err(SaveError::Corrupt("map does not match its size".to_string()));
+    }
+    let dimensions = miasma.dimensions();
+    if dimensions.x != map.width || dimensions.y != map.height {
+        return Err(SaveError::Corrupt("miasma does not match the map".to_string()));
+    }
+    let inside = |pos: &Point2<usize>| pos.x < map.width && pos.y < map.height;
+    if !inside(&session.pos) || !session.entity_manager.physics.values().all(|physics| inside(&physics.position)) {
+        return Err(SaveError::Corrupt("position outside of the map".to_string()));
+    }
+    let physics = &session.entity_manager.physics;
+    if !session.wear.iter().all(|(id, worn)| physics.contains_key(id) && (0f32..1f32).contains(worn)) {
+        return Err(SaveError::Corrupt("wear of a missing entity or out of range".to_string()));
+    }
+    Ok(GameState::restore(session.pos, session.map, session.entity_manager, miasma, session.wear))
+}